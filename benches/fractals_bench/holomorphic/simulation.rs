@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, BenchmarkId, Criterion};
-use fractals::holomorphic::{simulation::Simulation, test::mandelbrot};
+use fractals::holomorphic::{simulation::Simulation, test::{mandelbrot, mandelbrot_f64}};
 use std::time::Duration;
 
 /// Calculate required messurement time from pessimistic estimated throughput.
@@ -13,6 +13,7 @@ fn expected_messurement_time(elements: u32, n: u64, sample_size: usize, expected
 pub fn bench_simulation(c: &mut Criterion) {
     let mut mandelbrot_800_600 = mandelbrot(800, 600);
     let mut mandelbrot_1920_1080 = mandelbrot(1920, 1080);
+    let mut mandelbrot_f64_800_600 = mandelbrot_f64(800, 600);
 
     let sample_size = 10;
     let expected_throughput_s: f64 = 170e6;  // pixel * iterations / second
@@ -35,6 +36,13 @@ pub fn bench_simulation(c: &mut Criterion) {
             b.iter(|| mandelbrot_1920_1080.steps(black_box(iterations as i32)));
         });
     }
+    for iterations in [1, 10, 100, 1000].iter() {
+        group.measurement_time(expected_messurement_time(800*600, *iterations, sample_size, expected_throughput_s));
+        group.throughput(criterion::Throughput::Elements(800*600*iterations));
+        group.bench_with_input(BenchmarkId::new("mandelbrot_f64_800x600", iterations), iterations, |b, &iterations| {
+            b.iter(|| mandelbrot_f64_800_600.steps(black_box(iterations as i32)));
+        });
+    }
     group.finish();
 }
 
@@ -0,0 +1,188 @@
+extern crate sdl2;
+
+use fractals::cli::{parse_numbers, parse_size};
+use fractals::holomorphic::buddhabrot::Buddhabrot;
+use fractals::holomorphic::plane::Plane;
+use fractals::sdl::image::image_into_texture;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::video::FullscreenType;
+use std::path::PathBuf;
+use std::time::Instant;
+
+
+/** Values of c sampled between two refreshes of the window **/
+const BATCH: u64 = 200_000;
+
+const USAGE: &str = "Renders the density of the escaping orbits of z^2 + c (Buddhabrot, Nebulabrot)
+
+Usage: buddhabrot [OPTIONS]
+
+Options:
+  --viewport RE_MIN,RE_MAX,IM_MIN,IM_MAX  clipping of the complex plane [default: -2,1,-1.2,1.2]
+  --size WIDTHxHEIGHT                     resolution in pixels [default: 800x640]
+  --limits RED,GREEN,BLUE                 maximal iterations of the orbits recorded per channel [default: 5000,500,50]
+  --iterations N                          the same limit for all channels (the gray Buddhabrot)
+  --samples N                             random values of c [default: 20000000]
+  --gamma GAMMA                           tone mapping, below 1 brightens faint orbits [default: 0.5]
+  --output FILE                           render into a .png, .ppm or .gif file instead of refining in a window
+  --help                                  print this help";
+
+struct Options {
+    plane: Plane<f64>,
+    limits: [i32; 3],
+    samples: u64,
+    gamma: f32,
+    output: Option<PathBuf>,
+}
+
+fn parse_limit(value: &str) -> Result<i32, String> {
+    match value.trim().parse::<i32>() {
+        Ok(limit) if limit > 0 => Ok(limit),
+        Ok(_) => Err(format!("'{}': the limit must be positive", value)),
+        Err(e) => Err(format!("'{}': {}", value, e)),
+    }
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        plane: Plane {re_min: -2.0, re_max: 1.0, im_min: -1.2, im_max: 1.2, rotation: 0.0, width: 800, height: 640},
+        limits: [5000, 500, 50],
+        samples: 20_000_000,
+        gamma: 0.5,
+        output: None,
+    };
+
+    while let Some(arg) = args.next() {
+        if arg == "--help" {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+        let value = args.next().ok_or(format!("{} requires a value", arg))?;
+        match arg.as_str() {
+            "--viewport" => {
+                let v = parse_numbers(&value, 4)?;
+                options.plane = Plane {re_min: v[0], re_max: v[1], im_min: v[2], im_max: v[3], ..options.plane};
+            }
+            "--size" => {
+                let (width, height) = parse_size(&value)?;
+                options.plane = Plane {width, height, ..options.plane};
+            }
+            "--limits" => {
+                let limits = value.split(',').map(parse_limit).collect::<Result<Vec<_>, _>>()?;
+                options.limits = limits.try_into().map_err(|_| format!("'{}': expected 3 comma separated limits", value))?;
+            }
+            "--iterations" => {
+                options.limits = [parse_limit(&value)?; 3];
+            }
+            "--samples" => {
+                options.samples = value.parse::<u64>().map_err(|e| format!("'{}': {}", value, e))?;
+            }
+            "--gamma" => {
+                options.gamma = value.parse::<f32>().map_err(|e| format!("'{}': {}", value, e))?;
+                if options.gamma <= 0.0 {
+                    return Err(format!("'{}': the gamma must be positive", value));
+                }
+            }
+            "--output" => {
+                options.output = Some(PathBuf::from(value));
+            }
+            _ => return Err(format!("unknown option {}\n\n{}", arg, USAGE)),
+        }
+    }
+    Ok(options)
+}
+
+fn main() -> Result<(), String> {
+    let Options {plane, limits, samples, gamma, output} = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    let mut buddhabrot = Buddhabrot::new(plane, limits);
+
+    if let Some(path) = output {
+        let start = Instant::now();
+        while buddhabrot.samples < samples {
+            buddhabrot.sample(BATCH.min(samples - buddhabrot.samples));
+            eprint!("\r{} of {} samples", buddhabrot.samples, samples);
+        }
+        eprintln!(" in {:.1} s", start.elapsed().as_secs_f32());
+        buddhabrot.image(gamma).save(&path)?;
+        println!("Saved {}", path.display());
+        return Ok(());
+    }
+
+    let sdl_context = sdl2::init()?;
+    let video_subsys = sdl_context.video()?;
+    let window = video_subsys
+        .window(
+            "Buddhabrot",
+            plane.width as u32,
+            plane.height as u32,
+        )
+        .resizable()
+        .position_centered()
+        .opengl()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
+    let mut events = sdl_context.event_pump()?;
+
+    println!("The image refines until {} values of c are sampled…", samples);
+    println!("Press [S] to save the image as buddhabrot.png…");
+    println!("Press [F11] to toggle fullscreen…");
+    println!("Press [Esc] to quit…");
+
+    /* the texture is scaled to the window, so resizing it doesn't restart the sampling */
+    let mut frame = image_into_texture(&texture_creator, &buddhabrot.image(gamma))?;
+
+    'main: loop {
+        /* the sampling continues between the events, and waits for them once it is complete */
+        let event = if buddhabrot.samples < samples {
+            events.poll_event()
+        } else {
+            Some(events.wait_event())
+        };
+
+        match event {
+            Some(Event::Quit { .. }) => break 'main,
+
+            Some(Event::KeyDown { keycode: Some(keycode), .. }) => {
+                if keycode == Keycode::Escape {
+                    break 'main;
+                } else if keycode == Keycode::F11 {
+                    let fullscreen = canvas.window().fullscreen_state() == FullscreenType::Off;
+                    let _ = canvas.window_mut().set_fullscreen(if fullscreen { FullscreenType::Desktop } else { FullscreenType::Off });
+                } else if keycode == Keycode::S {
+                    let path = PathBuf::from("buddhabrot.png");
+                    match buddhabrot.image(gamma).save(&path) {
+                        Ok(()) => println!("Saved {}", path.display()),
+                        Err(message) => println!("{}", message),
+                    }
+                }
+            }
+
+            Some(_) => {}
+
+            None => {
+                buddhabrot.sample(BATCH.min(samples - buddhabrot.samples));
+                eprint!("\r{} of {} samples", buddhabrot.samples, samples);
+                if buddhabrot.samples >= samples {
+                    eprintln!();
+                }
+                /* the image only changes with the samples, other events present it again */
+                frame = image_into_texture(&texture_creator, &buddhabrot.image(gamma))?;
+            }
+        }
+
+        canvas.copy(&frame, None, None)?;
+        canvas.present();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,248 @@
+extern crate sdl2;
+
+use fractals::cli::{parse_numbers, parse_size};
+use fractals::coloring::{color_space::ColorSpace, domain_coloring::Enhancement};
+use fractals::holomorphic::formula::Formula;
+use fractals::holomorphic::plane::{Aspect, Plane};
+use fractals::holomorphic::portrait::portrait;
+use fractals::holomorphic::r#fn::HolomorphicFn;
+use fractals::image::Image;
+use fractals::sdl::image::image_into_texture;
+use num::complex::Complex;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::{FullscreenType, WindowContext};
+use std::io::{stdin, stdout, Write};
+use std::path::PathBuf;
+
+
+/** Zoom factor per step of the mouse wheel **/
+const WHEEL_ZOOM: f64 = 0.8;
+
+/** Mouse movement (in pixels), below which a button press counts as click instead of drag **/
+const DRAG_THRESHOLD: i32 = 3;
+
+const USAGE: &str = "Explores a holomorphic function f(z, c) by its enhanced phase portrait
+
+Usage: domain [OPTIONS]
+
+Options:
+  --formula FORMULA                       the function, e.g. \"sin(z)\" [default: (z^2 - 1)*(z - 2 - i)^2 / (z^2 + 2 + 2i)]
+  --c RE,IM                               the parameter c of the formula [default: 0,0]
+  --viewport RE_MIN,RE_MAX,IM_MIN,IM_MAX  clipping of the complex plane [default: -3,3,-2.25,2.25]
+  --color-space srgb|linear|oklab|oklch   hues of HSL (srgb, linear) or of OKLCH with constant lightness (oklab, oklch)
+                                          [default: oklch]
+  --size WIDTHxHEIGHT                     resolution in pixels [default: 800x600]
+  --output FILE                           render into a .png, .ppm or .gif file instead of opening a window
+  --help                                  print this help";
+
+struct Options {
+    formula: Formula<f64>,
+    c: Complex<f64>,
+    plane: Plane<f64>,
+    space: ColorSpace,
+    output: Option<PathBuf>,
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        formula: Formula::parse("(z^2 - 1)*(z - 2 - i)^2 / (z^2 + 2 + 2i)").expect("valid formula"),
+        c: Complex::new(0.0, 0.0),
+        plane: Plane {re_min: -3.0, re_max: 3.0, im_min: -2.25, im_max: 2.25, rotation: 0.0, width: 800, height: 600},
+        space: ColorSpace::Oklch,
+        output: None,
+    };
+
+    while let Some(arg) = args.next() {
+        if arg == "--help" {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+        let value = args.next().ok_or(format!("{} requires a value", arg))?;
+        match arg.as_str() {
+            "--formula" => {
+                options.formula = Formula::parse(&value)?;
+            }
+            "--c" => {
+                let v = parse_numbers(&value, 2)?;
+                options.c = Complex::new(v[0], v[1]);
+            }
+            "--viewport" => {
+                let v = parse_numbers(&value, 4)?;
+                options.plane = Plane {re_min: v[0], re_max: v[1], im_min: v[2], im_max: v[3], ..options.plane};
+            }
+            "--color-space" => {
+                options.space = ColorSpace::parse(&value)?;
+            }
+            "--size" => {
+                let (width, height) = parse_size(&value)?;
+                options.plane = Plane {width, height, ..options.plane};
+            }
+            "--output" => {
+                options.output = Some(PathBuf::from(value));
+            }
+            _ => return Err(format!("unknown option {}\n\n{}", arg, USAGE)),
+        }
+    }
+    Ok(options)
+}
+
+/** Asks for a formula in the terminal, until it is valid or empty **/
+fn prompt_formula() -> Option<Formula<f64>> {
+    loop {
+        print!("Enter a formula f(z, c) or nothing to keep the current one: ");
+        let _ = stdout().flush();
+        let mut line = String::new();
+        if stdin().read_line(&mut line).is_err() || line.trim().is_empty() {
+            return None;
+        }
+        match Formula::parse(line.trim()) {
+            Ok(formula) => return Some(formula),
+            Err(message) => println!("{}", message),
+        }
+    }
+}
+
+fn show<'a>(canvas: &mut WindowCanvas, texture_creator: &'a TextureCreator<WindowContext>, image: &Image) -> Result<Texture<'a>, String> {
+    let texture = image_into_texture(texture_creator, image)?;
+    canvas.copy(&texture, None, None)?;
+    canvas.present();
+    Ok(texture)
+}
+
+fn main() -> Result<(), String> {
+    let Options {mut formula, c, mut plane, mut space, output} = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    let mut enhancement = Enhancement {modulus: true, phase: true, grid: false};
+    let mut f: Box<dyn HolomorphicFn<f64>> = Box::new(formula.clone().into_fn());
+
+    if let Some(path) = output {
+        portrait(f.as_ref(), c, &plane, enhancement, space).save(&path)?;
+        println!("Saved {}", path.display());
+        return Ok(());
+    }
+
+    let sdl_context = sdl2::init()?;
+    let video_subsys = sdl_context.video()?;
+    let window = video_subsys
+        .window(
+            "Domain coloring",
+            plane.width as u32,
+            plane.height as u32,
+        )
+        .resizable()
+        .position_centered()
+        .opengl()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
+    let mut events = sdl_context.event_pump()?;
+
+    println!("Press [M] to toggle the contour lines of the modulus…");
+    println!("Press [A] to toggle the isochromatic lines of the argument…");
+    println!("Press [G] to toggle the conformal grid of Re f and Im f…");
+    println!("Press [I] to cycle through the color spaces…");
+    println!("Press [F] to enter another formula in the terminal…");
+    println!("Press [S] to save the portrait as domain.png…");
+    println!("Press [F11] to toggle fullscreen…");
+    println!("[Scroll] to zoom around the cursor…");
+    println!("[Drag] with the left mouse button to pan…");
+    println!("[Klick] any coordinate for debug output…");
+    println!("Press [Esc] to quit…");
+
+    let mut image = portrait(f.as_ref(), c, &plane, enhancement, space);
+    let mut frame = show(&mut canvas, &texture_creator, &image)?;
+    /* coordinate where the current drag started */
+    let mut drag: Option<(i32, i32)> = None;
+
+    'main: loop {
+        let mut changed = false;
+        match events.wait_event() {
+            Event::Quit { .. } => break 'main,
+
+            Event::KeyDown { keycode: Some(keycode), .. } => {
+                if keycode == Keycode::Escape {
+                    break 'main;
+                } else if keycode == Keycode::F11 {
+                    let fullscreen = canvas.window().fullscreen_state() == FullscreenType::Off;
+                    let _ = canvas.window_mut().set_fullscreen(if fullscreen { FullscreenType::Desktop } else { FullscreenType::Off });
+                } else if keycode == Keycode::M {
+                    enhancement.modulus = !enhancement.modulus;
+                    changed = true;
+                } else if keycode == Keycode::A {
+                    enhancement.phase = !enhancement.phase;
+                    changed = true;
+                } else if keycode == Keycode::G {
+                    enhancement.grid = !enhancement.grid;
+                    changed = true;
+                } else if keycode == Keycode::I {
+                    let k = ColorSpace::ALL.iter().position(|&known| known == space).unwrap_or(0);
+                    space = ColorSpace::ALL[(k + 1) % ColorSpace::ALL.len()];
+                    println!("Color space {}", space.name());
+                    changed = true;
+                } else if keycode == Keycode::F {
+                    if let Some(reformulated) = prompt_formula() {
+                        formula = reformulated;
+                        f = Box::new(formula.clone().into_fn());
+                        changed = true;
+                    }
+                } else if keycode == Keycode::S {
+                    let path = PathBuf::from("domain.png");
+                    match image.save(&path) {
+                        Ok(()) => println!("Saved {}", path.display()),
+                        Err(message) => println!("{}", message),
+                    }
+                }
+            }
+
+            Event::MouseWheel { y: 0, .. } => {}
+
+            Event::MouseWheel { y, mouse_x, mouse_y, .. } => {
+                plane = plane.zoom(mouse_x, mouse_y, WHEEL_ZOOM.powi(y));
+                changed = true;
+            }
+
+            Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                drag = Some((x, y));
+            }
+
+            Event::MouseButtonUp { mouse_btn: MouseButton::Left, x, y, .. } => {
+                if let Some((x0, y0)) = drag.take() {
+                    if (x-x0).abs() < DRAG_THRESHOLD && (y-y0).abs() < DRAG_THRESHOLD {
+                        let z = plane.xy_to_c(x.max(0) as usize, y.max(0) as usize);
+                        println!("z = {}, f(z) = {}, f'(z) = {}", z, f(z, c), f.df_dz(z, c));
+                    } else {
+                        plane = plane.pan(x-x0, y-y0);
+                        changed = true;
+                    }
+                }
+            }
+
+            Event::Window { win_event: sdl2::event::WindowEvent::Resized(w, h), .. } => {
+                plane = plane.resize(w as usize, h as usize, Aspect::Fit);
+                changed = true;
+            }
+
+            _ => {}
+        }
+
+        if changed {
+            image = portrait(f.as_ref(), c, &plane, enhancement, space);
+            frame = show(&mut canvas, &texture_creator, &image)?;
+        } else {
+            canvas.copy(&frame, None, None)?;
+            canvas.present();
+        }
+    }
+
+    Ok(())
+}
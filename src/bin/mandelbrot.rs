@@ -1,20 +1,427 @@
 extern crate sdl2;
 
-use fractals::holomorphic::dynamic::{Juliaset, Mandelbrot};
-use fractals::holomorphic::plane::Plane;
+use fractals::coloring::{color_space::ColorSpace, palette::Palette};
+use fractals::holomorphic::dynamic::{DynamicKind, HolomorphicDynamic, Juliaset, Mandelbrot, Newton};
+use fractals::holomorphic::formula::Formula;
+use fractals::holomorphic::r#fn::HolomorphicFn;
+use fractals::holomorphic::plane::{Aspect, Plane};
+use fractals::holomorphic::session::{Session, PERTURBATION_PIXEL_SIZE};
 use fractals::holomorphic::simulation::Simulation;
-use fractals::holomorphic::visualize::Visualize;
+use fractals::holomorphic::tiles::{Tiles, CACHED_TILES};
+use fractals::holomorphic::viewport::Viewport;
+use fractals::holomorphic::visualize::{Coloring, Visualize};
+use fractals::holomorphic::worker::Worker;
+use fractals::sdl::image::image_into_texture;
 use num::complex::Complex;
 use sdl2::event::{Event, EventType};
-use sdl2::keyboard::Keycode;
+use sdl2::gfx::primitives::DrawRenderer;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
 use sdl2::pixels::Color;
-use sdl2::video::FullscreenType;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::{FullscreenType, WindowContext};
 use std::cell::RefCell;
-use std::io::{stdout, Write};
+use std::io::{stdin, stdout, Write};
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
 
+/** Precision of the explored planes: f64 allows zooming ~10^9 times deeper than f32 **/
+type Real = f64;
+
+/** Zoom factor per step of the mouse wheel **/
+const WHEEL_ZOOM: Real = 0.8;
+
+/** Zoom factor per step of the mouse wheel, when the views are tiled, whose zoom levels are powers of 2 apart **/
+const TILED_WHEEL_ZOOM: Real = 0.5;
+
+/** Rotation (in degrees) per key press or step of the mouse wheel with [Ctrl] **/
+const ROTATION_STEP: Real = 15.0;
+
+/** Drags shorter than this (in pixels) are treated as clicks **/
+const DRAG_THRESHOLD: i32 = 3;
+
+/** The Julia set preview covers this fraction of the window width and height **/
+const PREVIEW_FRACTION: u32 = 3;
+
+/** Factor of the palette density per key press **/
+const DENSITY_STEP: f32 = 1.25;
+
+/** Fraction of a palette cycle, by which the palette is shifted per key press **/
+const OFFSET_STEP: f32 = 1.0 / 16.0;
+
+/** Interval (in ms) for checking the progress of background simulations **/
+const PROGRESS_MS: u32 = 30;
+
+/** The preview iterates at least this often, even if the Mandelbrot set was iterated less **/
+const PREVIEW_ITERATIONS: i32 = 100;
+
+/** The rectangle of the preview in the upper right corner of the canvas **/
+fn preview_rect(canvas: &WindowCanvas) -> Rect {
+    let (width, height) = canvas.output_size().unwrap_or((800, 600));
+    let (w, h) = ((width / PREVIEW_FRACTION).max(1), (height / PREVIEW_FRACTION).max(1));
+    Rect::new((width - w) as i32 - 10, 10, w, h)
+}
+
+/** The Julia set of the formula for a fixed c, as simulated for the preview **/
+fn julia_preview(formula: &Formula<Real>, c: Complex<Real>, rect: Rect) -> HolomorphicDynamic<Real> {
+    let (width, height) = (rect.width().max(1) as usize, rect.height().max(1) as usize);
+    let im = 2.0 * height as Real / width as Real;
+    let plane = Plane {re_min: -2.0, re_max: 2.0, im_min: -im, im_max: im, rotation: 0.0, width, height};
+    let formula = formula.clone();
+    Juliaset::new(plane, move |z, _c| { formula.eval(z, c) })
+}
+
+/** The absolute c at a coordinate, also for perturbed dynamics (as far as Real allows) **/
+fn absolute_c(dynamic: &HolomorphicDynamic<Real>, x: i32, y: i32) -> Complex<Real> {
+    let c = dynamic.plane.xy_to_c(x.max(0) as usize, y.max(0) as usize);
+    match &dynamic.perturbation {
+        None => c,
+        Some(perturbation) => {
+            let (re, im) = perturbation.center.to_f64();
+            c + Complex::new(re, im)
+        }
+    }
+}
+
+/** Presents a frame, optionally with the Julia set preview and the rubber band of a selection **/
+fn present(canvas: &mut WindowCanvas, frame: &Texture, preview: Option<&Texture>, selection: Option<(i32, i32, i32, i32)>) {
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    let _ = canvas.copy(frame, None, None);
+    if let Some(preview) = preview {
+        let rect = preview_rect(canvas);
+        let _ = canvas.copy(preview, None, rect);
+        let _ = canvas.rectangle(rect.left() as i16, rect.top() as i16, rect.right() as i16, rect.bottom() as i16, Color::RGB(255, 255, 255));
+    }
+    if let Some((x0, y0, x1, y1)) = selection {
+        let _ = canvas.rectangle(x0 as i16, y0 as i16, x1 as i16, y1 as i16, Color::RGB(255, 255, 255));
+    }
+    canvas.present();
+}
+
+/** Visualizes the dynamic into a new frame and presents it **/
+fn show<'a>(canvas: &mut WindowCanvas, texture_creator: &'a TextureCreator<WindowContext>, dynamic: &HolomorphicDynamic<Real>, view: Coloring, palette: &Palette, preview: Option<&Texture>) -> Result<Texture<'a>, String> {
+    let frame = image_into_texture(texture_creator, &dynamic.visualize(view, palette))?;
+    present(canvas, &frame, preview, None);
+    Ok(frame)
+}
+
+const USAGE: &str = "Usage: mandelbrot [--session FILE] [--formula FORMULA] [--julia RE,IM] [--palette NAME|FILE]… [--aspect ASPECT]
+                  [--tiles N] [--tile-cache DIR] [--help]
+
+Options:
+  --session FILE       continue a saved session, the following options override it
+  --formula FORMULA    the iterated function f(z, c), e.g. \"z^3 + c\" or \"sin(z)*c\" [default: z^2 + c]
+  --julia RE,IM        the c of the Julia set [default: 0,1]
+  --palette NAME|FILE  colors of the iterations: default, ultra, fire, gray or a .ggr, .map, .csv or .json file,
+                       repeat it to cycle through several files with [P]
+  --aspect ASPECT      how views adapt to the window: fit shows the whole view, fill covers the window with it,
+                       both with square pixels, stretch distorts it [default: fit]
+  --tiles N            compute the Mandelbrot set in tiles of 256×256 pixels and keep N of them in memory, so views
+                       visited before appear at once; zooms snap to powers of 2
+  --tile-cache DIR     also keep the tiles in DIR across runs (with --tiles 64, unless given)
+  --help               print this help";
+
+struct Options {
+    session: Session,
+    restored: bool,              // whether the session was loaded from a file
+    palettes: Vec<Palette>,      // given in addition to the builtin ones
+    aspect: Aspect,              // of views in the window
+    tiling: Option<(usize, Option<PathBuf>)>,  // tiles kept in memory and the directory of the disk cache
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut session = Session::default();
+    let mut restored = false;
+    let mut palettes = Vec::new();
+    let mut aspect = Aspect::Fit;
+    let mut tiling: Option<(usize, Option<PathBuf>)> = None;
+    while let Some(arg) = args.next() {
+        if arg == "--help" {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+        let value = args.next().ok_or(format!("{} requires a value\n\n{}", arg, USAGE))?;
+        match arg.as_str() {
+            "--session" => {
+                session = Session::load(value.as_ref())?;
+                restored = true;
+            }
+            "--formula" => session.formula = Formula::parse(&value)?,
+            "--julia" => {
+                let (re, im) = value.split_once(',').ok_or(format!("'{}': expected RE,IM", value))?;
+                let parse = |x: &str| x.trim().parse::<Real>().map_err(|e| format!("'{}': {}", x, e));
+                session.julia = Complex::new(parse(re)?, parse(im)?);
+            }
+            "--palette" => {
+                session.palette = Palette::find(&value)?;
+                palettes.push(session.palette.clone());
+            }
+            "--aspect" => aspect = Aspect::parse(&value)?,
+            "--tiles" => {
+                let capacity = value.parse::<usize>().map_err(|e| format!("'{}': {}", value, e))?;
+                if capacity == 0 {
+                    return Err(format!("'{}': at least one tile has to be kept", value));
+                }
+                tiling = Some((capacity, tiling.and_then(|(_, directory)| directory)));
+            }
+            "--tile-cache" => {
+                tiling = Some((tiling.map_or(CACHED_TILES, |(capacity, _)| capacity), Some(PathBuf::from(value))));
+            }
+            _ => return Err(format!("unknown option {}\n\n{}", arg, USAGE)),
+        }
+    }
+    Ok(Options {session, restored, palettes, aspect, tiling})
+}
+
+/** Asks for a formula in the terminal, until it is valid or empty **/
+fn prompt_formula() -> Option<Formula<Real>> {
+    loop {
+        print!("Enter a formula f(z, c) or nothing to keep the current one: ");
+        let _ = stdout().flush();
+        let mut line = String::new();
+        if stdin().read_line(&mut line).is_err() || line.trim().is_empty() {
+            return None;
+        }
+        match Formula::parse(line.trim()) {
+            Ok(formula) => return Some(formula),
+            Err(message) => println!("{}", message),
+        }
+    }
+}
+
+/** Asks for a view in the terminal, until it is valid or empty **/
+fn prompt_viewport() -> Option<Viewport> {
+    loop {
+        print!("Enter a view RE,IM,SCALE or nothing to keep the current one: ");
+        let _ = stdout().flush();
+        let mut line = String::new();
+        if stdin().read_line(&mut line).is_err() || line.trim().is_empty() {
+            return None;
+        }
+        match Viewport::parse(line.trim()) {
+            Ok(viewport) => return Some(viewport),
+            Err(message) => println!("{}", message),
+        }
+    }
+}
+
+/** Asks for the file of a session in the terminal **/
+fn prompt_session_path(action: &str) -> PathBuf {
+    print!("Enter a file to {} the session [session.toml]: ", action);
+    let _ = stdout().flush();
+    let mut line = String::new();
+    match stdin().read_line(&mut line) {
+        Ok(_) if !line.trim().is_empty() => PathBuf::from(line.trim()),
+        _ => PathBuf::from("session.toml"),
+    }
+}
+
+/** The tiles of the Mandelbrot set of the formula, if the views are tiled **/
+fn tile_engine(tiling: &Option<(usize, Option<PathBuf>)>, formula: &Formula<Real>) -> Result<Option<Arc<Mutex<Tiles>>>, String> {
+    let Some((capacity, directory)) = tiling else { return Ok(None) };
+    let session = Session {kind: DynamicKind::Mandelbrot, formula: formula.clone(), ..Session::default()};
+    Ok(Some(Arc::new(Mutex::new(Tiles::new(&session, *capacity, directory.clone())?))))
+}
+
+/** The tiles for the dynamic, only the Mandelbrot set is tiled **/
+fn tiled<'a>(tiles: &'a Option<Arc<Mutex<Tiles>>>, dynamic: &Rc<RefCell<HolomorphicDynamic<Real>>>, mandelbrot: &Rc<RefCell<HolomorphicDynamic<Real>>>) -> Option<&'a Arc<Mutex<Tiles>>> {
+    tiles.as_ref().filter(|_| Rc::ptr_eq(dynamic, mandelbrot))
+}
+
+/** Dynamics being simulated in the background **/
+type Workers = Vec<(Rc<RefCell<HolomorphicDynamic<Real>>>, Worker<Real>)>;
+
+/** The iteration, which the dynamic is (being) simulated up to **/
+fn target(workers: &Workers, dynamic: &Rc<RefCell<HolomorphicDynamic<Real>>>) -> i32 {
+    workers.iter()
+        .find(|(simulated, _)| Rc::ptr_eq(simulated, dynamic))
+        .map_or(dynamic.borrow().i, |(_, worker)| worker.target())
+}
+
+/** Replaces the dynamic by a restarted one, which is simulated up to the iteration in the background
+ *  - a previous background simulation of the dynamic is cancelled
+ **/
+fn restart(workers: &mut Workers, dynamic: &Rc<RefCell<HolomorphicDynamic<Real>>>, restarted: HolomorphicDynamic<Real>, iterations: i32) {
+    workers.retain(|(simulated, _)| !Rc::ptr_eq(simulated, dynamic));
+    workers.push((Rc::clone(dynamic), Worker::spawn(restarted.clone(), iterations)));
+    *dynamic.borrow_mut() = restarted;
+}
+
+/** Simulates the next iteration, in the background if the dynamic is simulated there anyway **/
+fn step(workers: &Workers, dynamic: &Rc<RefCell<HolomorphicDynamic<Real>>>) {
+    match workers.iter().find(|(simulated, _)| Rc::ptr_eq(simulated, dynamic)) {
+        Some((_, worker)) => worker.extend(1),
+        None => dynamic.borrow_mut().step(),
+    }
+}
+
+/** Restarts the dynamic up to the same iteration with its derivative tracked, unless it already is
+ *  - only the distance coloring needs the derivative, so the other colorings don't pay for it
+ **/
+fn track_derivative(workers: &mut Workers, dynamic: &Rc<RefCell<HolomorphicDynamic<Real>>>) {
+    if dynamic.borrow().derivative {
+        return;
+    }
+    let restarted = {
+        let dynamic = dynamic.borrow();
+        let mut restarted = dynamic.with_plane(dynamic.plane);
+        restarted.derivative = true;
+        restarted
+    };
+    let iterations = target(workers, dynamic);
+    restart(workers, dynamic, restarted, iterations);
+}
+
+/** Takes over the progress of the background simulations
+ *  - snapshots of the full resolution replace their dynamic
+ *  - returns a new frame, when there was progress of the active dynamic
+ **/
+fn progress<'a>(workers: &mut Workers, active: &Rc<RefCell<HolomorphicDynamic<Real>>>, canvas: &mut WindowCanvas, texture_creator: &'a TextureCreator<WindowContext>, view: Coloring, palette: &Palette, preview: Option<&Texture>) -> Result<Option<Texture<'a>>, String> {
+    let mut frame = None;
+    let mut k = 0;
+    while k < workers.len() {
+        let (dynamic, worker) = &workers[k];
+        let Some(snapshot) = worker.latest() else {
+            k += 1;
+            continue;
+        };
+        let full = {
+            let plane = dynamic.borrow().plane;
+            (snapshot.dynamic.plane.width, snapshot.dynamic.plane.height) == (plane.width, plane.height)
+        };
+        if Rc::ptr_eq(dynamic, active) {
+            frame = Some(show(canvas, texture_creator, &snapshot.dynamic, view, palette, preview)?);
+        }
+        if full {
+            *dynamic.borrow_mut() = snapshot.dynamic;
+        }
+        if snapshot.complete {
+            /* iterations requested after the worker finished */
+            let missing = worker.target() - dynamic.borrow().i;
+            if missing > 0 {
+                dynamic.borrow_mut().steps(missing);
+            }
+            workers.remove(k);
+        } else {
+            k += 1;
+        }
+    }
+    Ok(frame)
+}
+
+/** Replaces the function of the dynamic, simulated up to the same iteration in the background **/
+fn reformulate(workers: &mut Workers, dynamic: &Rc<RefCell<HolomorphicDynamic<Real>>>, f: Box<dyn HolomorphicFn<Real>>, perturbable: bool) {
+    let mut reformulated = {
+        let dynamic = dynamic.borrow();
+        match &dynamic.perturbation {
+            Some(perturbation) if !perturbable => {
+                /* back to absolute coordinates, as far as Real allows */
+                let (re, im) = perturbation.center.to_f64();
+                let mut absolute = Mandelbrot::new(dynamic.plane.translate(Complex::new(re, im)), f.clone());
+                absolute.derivative = dynamic.derivative;
+                absolute
+            }
+            _ => dynamic.with_plane(dynamic.plane),
+        }
+    };
+    reformulated.f = f;
+    let iterations = target(workers, dynamic);
+    restart(workers, dynamic, reformulated, iterations);
+}
+
+/** Replaces the dynamic by one on another plane, simulated up to the same iteration in the background
+ *  - with tiles, the plane is snapped to their grid and assembled from them, unless it is rotated or too deep
+ *  - on the same pixel grid (pans, resized windows), the computed coordinates are reused
+ *  - perturbable tells, whether the function is z² + c
+ **/
+fn recalculate(workers: &mut Workers, dynamic: &Rc<RefCell<HolomorphicDynamic<Real>>>, plane: Plane<Real>, perturbable: bool, tiles: Option<&Arc<Mutex<Tiles>>>) {
+    let snapped = tiles.zip(Tiles::snap(&plane)).filter(|_| dynamic.borrow().perturbation.is_none());
+    if let Some((tiles, plane)) = snapped {
+        let iterations = target(workers, dynamic);
+        /* the snapshots of the worker replace it */
+        let placeholder = dynamic.borrow().with_plane(plane);
+        workers.retain(|(simulated, _)| !Rc::ptr_eq(simulated, dynamic));
+        workers.push((Rc::clone(dynamic), Worker::spawn_tiled(Arc::clone(tiles), plane, iterations)));
+        *dynamic.borrow_mut() = placeholder;
+        return;
+    }
+    let recalculated = {
+        let dynamic = dynamic.borrow();
+        let deep = plane.pixel_size().abs() < PERTURBATION_PIXEL_SIZE;
+        let switching = deep && perturbable && dynamic.kind == DynamicKind::Mandelbrot && dynamic.perturbation.is_none();
+        match dynamic.reuse(plane) {
+            Some(reused) if !switching => reused,
+            _ if switching => {
+                println!("Switching to perturbation for deeper zooms…");
+                dynamic.with_plane(plane).perturbed()
+            }
+            _ => dynamic.with_plane(plane),
+        }
+    };
+    let iterations = target(workers, dynamic);
+    restart(workers, dynamic, recalculated, iterations);
+}
+
+/** Replaces the dynamic by one on a view in the same resolution, simulated up to the same iteration in the background **/
+fn go_to(workers: &mut Workers, dynamic: &Rc<RefCell<HolomorphicDynamic<Real>>>, viewport: &Viewport, perturbable: bool) {
+    let moved = {
+        let dynamic = dynamic.borrow();
+        let (width, height) = (dynamic.plane.width, dynamic.plane.height);
+        let deep = viewport.scale / (height as Real) < PERTURBATION_PIXEL_SIZE;
+        dynamic.with_viewport(viewport, width, height, deep && perturbable && dynamic.kind == DynamicKind::Mandelbrot)
+    };
+    let iterations = target(workers, dynamic);
+    restart(workers, dynamic, moved, iterations);
+}
+
+/** Restores the dynamic of the kind of the session in a window of the given size, the others take over its formula
+ *  - returns the restored dynamic
+ **/
+fn restore(workers: &mut Workers, [mandelbrot, juliaset, newton]: [&Rc<RefCell<HolomorphicDynamic<Real>>>; 3], session: &Session, (width, height): (usize, usize), aspect: Aspect) -> Result<Rc<RefCell<HolomorphicDynamic<Real>>>, String> {
+    let restored = Session {plane: session.plane.resize(width, height, aspect), ..session.clone()}.dynamic()?;
+    let dynamic = match session.kind {
+        DynamicKind::Mandelbrot => mandelbrot,
+        DynamicKind::Juliaset => juliaset,
+        DynamicKind::Newton => newton,
+    };
+    restart(workers, dynamic, restored, session.iterations);
+
+    let perturbable = session.formula.is_quadratic();
+    if !Rc::ptr_eq(dynamic, mandelbrot) {
+        reformulate(workers, mandelbrot, Box::new(session.formula.clone().into_fn()), perturbable);
+    }
+    if !Rc::ptr_eq(dynamic, juliaset) {
+        let (formula, julia_c) = (session.formula.clone(), session.julia);
+        reformulate(workers, juliaset, Box::new(move |z, _c| { formula.eval(z, julia_c) }), perturbable);
+    }
+    Ok(Rc::clone(dynamic))
+}
+
 fn main() -> Result<(), String> {
+    let Options {session, restored, palettes: files, aspect, tiling} = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    let mut palettes = Palette::builtins();
+    for palette in files.into_iter().chain([session.palette.clone()]) {
+        if !palettes.iter().any(|known| known.name == palette.name) {
+            palettes.push(palette);
+        }
+    }
+    let mut palette = session.palette.clone();
+    let mut formula = session.formula.clone();
+    let mut julia_c = session.julia;
+    let mut relaxation = session.relaxation;
+    let mut perturbable = formula.is_quadratic();
+    let mut tiles = tile_engine(&tiling, &formula)?;
+
     let sdl_context = sdl2::init()?;
     let video_subsys = sdl_context.video()?;
     let window = video_subsys
@@ -30,6 +437,7 @@ fn main() -> Result<(), String> {
         .map_err(|e| e.to_string())?;
 
     let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
 
     canvas.set_draw_color(Color::RGB(0, 0, 0));
     canvas.clear();
@@ -41,38 +449,100 @@ fn main() -> Result<(), String> {
     println!("Press [F1] and [1] to toggle between Mandelbrot and Julia set…");
     println!("Press [Space] ([F1 or [1]]) to show state z of next iteration…");
     println!("Press [Enter] ([F2] or [2]) to show divergence of next iteration…");
+    println!("Press [F3] or [3] to show smooth divergence of next iteration…");
+    println!("Press [F4] or [4] to show the distance to the set of next iteration…");
+    println!("Press [F5] or [5] to show the period of interior points of next iteration…");
+    println!("Press [F6] or [6] to show the histogram-equalized divergence of next iteration…");
+    println!("Press [N] to show the basins of the Newton method for z³-1 of next iteration…");
+    println!("Press [J] to toggle a preview of the Julia set for the c under the cursor…");
+    println!("Press [F] to enter another formula in the terminal…");
+    println!("Press [P] ([Shift]+[P]) to cycle through the palettes…");
+    println!("Press [I] to cycle through the color spaces, in which the palette and z are colored…");
+    println!("Press [Up] or [Down] to change the density and [Left] or [Right] to shift the palette…");
+    println!("Press [S] or [L] to save or load the session in a file…");
+    println!("Press [V] to print the view for sharing and to enter another one…");
+    println!("Press [R] ([Shift]+[R]) to rotate the image counterclockwise (clockwise)…");
     println!("Press [F11] to toggle fullscreen…");
+    println!("[Scroll] to zoom around the cursor, with [Ctrl] to rotate around it…");
+    println!("[Drag] with the left mouse button to pan…");
+    println!("[Drag] with the right mouse button to zoom into a rectangle…");
     println!("[Klick] any coordinate for debug output…");
     println!("Press [Esc] to quit…");
 
-    let mut mandelbrot = {
-        let plane = Plane {re_min: -2.0, re_max: 0.55, im_min: -1.2, im_max: 1.2, width: 800, height: 600};
-        let f = |z, c| { z*z+c };
-        Rc::new(RefCell::new(Mandelbrot::new(plane, f)))
+    let mandelbrot = {
+        let plane = Plane {re_min: -2.0, re_max: 0.55, im_min: -1.2, im_max: 1.2, rotation: 0.0, width: 800, height: 600}.resize(800, 600, aspect);
+        let plane = tiles.as_ref().and_then(|_| Tiles::snap(&plane)).unwrap_or(plane);
+        Rc::new(RefCell::new(Mandelbrot::new(plane, formula.clone().into_fn())))
     };
 
-    let mut juliaset = {
-        let plane = Plane {re_min: -2.0, re_max: 2.0, im_min: -2.0, im_max: 2.0, width: 800, height: 600};
-        let f = |z, _c| { z*z+Complex::new(0.0,1.0 )};
+    let juliaset = {
+        let plane = Plane {re_min: -2.0, re_max: 2.0, im_min: -2.0, im_max: 2.0, rotation: 0.0, width: 800, height: 600}.resize(800, 600, aspect);
+        let formula = formula.clone();
+        let f = move |z, _c| { formula.eval(z, julia_c) };
         Rc::new(RefCell::new(Juliaset::new(plane, f)))
     };
 
+    let newton = {
+        let plane = Plane {re_min: -2.0, re_max: 2.0, im_min: -1.5, im_max: 1.5, rotation: 0.0, width: 800, height: 600}.resize(800, 600, aspect);
+        let roots = (0..3).map(|k| Complex::from_polar(1.0, k as Real * 2.0 * std::f64::consts::PI / 3.0)).collect();
+        Rc::new(RefCell::new(Newton::dynamic(plane, roots, relaxation)))
+    };
+
     let mut active_dynamic = Rc::clone(&mandelbrot);
+    let mut view = Coloring::Z;
+
+    /* mouse button and coordinate where the current drag started */
+    let mut drag: Option<(MouseButton, i32, i32)> = None;
+
+    /* the Julia set for the c under the cursor, simulated in the background until the cursor moves on */
+    let mut preview_enabled = false;
+    let mut preview: Option<Texture> = None;
+    let mut preview_worker: Option<Worker<Real>> = None;
 
+    let mut workers: Workers = Vec::new();
 
-    active_dynamic.borrow_mut().step();
-    active_dynamic.borrow().visualize_z(&mut canvas);
+
+    if restored {
+        let (width, height) = canvas.output_size()?;
+        active_dynamic = restore(&mut workers, [&mandelbrot, &juliaset, &newton], &session, (width as usize, height as usize), aspect)?;
+        view = session.coloring;
+    } else {
+        active_dynamic.borrow_mut().step();
+    }
+    let mut frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
 
     'main: loop {
+        if let Some(progressed) = progress(&mut workers, &active_dynamic, &mut canvas, &texture_creator, view, &palette, preview.as_ref())? {
+            frame = progressed;
+        }
+        if let Some(message) = tiles.as_ref().and_then(|tiles| tiles.lock().ok()?.disk_error()) {
+            println!("{}, tiles are no longer cached on disk", message);
+        }
+        if let Some(snapshot) = preview_worker.as_ref().and_then(|worker| worker.latest()) {
+            /* coarse passes first, then the full resolution */
+            if snapshot.complete {
+                preview_worker = None;
+            }
+            preview = Some(image_into_texture(&texture_creator, &snapshot.dynamic.visualize_smooth(&palette))?);
+            present(&mut canvas, &frame, preview.as_ref(), None);
+        }
+
         events.enable_event(EventType::KeyDown);
-        let event = events.wait_event();
+        let event = if workers.is_empty() && preview_worker.is_none() {
+            Some(events.wait_event())
+        } else {
+            events.wait_event_timeout(PROGRESS_MS)
+        };
         events.disable_event(EventType::KeyDown);
 
+        let Some(event) = event else { continue };
+
         match event {
             Event::Quit { .. } => break 'main,
 
             Event::KeyDown {
                 keycode: Some(keycode),
+                keymod,
                 ..
             } => {
                 if keycode == Keycode::Escape {
@@ -87,32 +557,238 @@ fn main() -> Result<(), String> {
 
                 } else if keycode == Keycode::F1 {
                     active_dynamic = Rc::clone(&mandelbrot);
-                    active_dynamic.borrow_mut().step();
-                    active_dynamic.borrow().visualize_z(&mut canvas);
+                    view = Coloring::Z;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
                 } else if keycode == Keycode::Num1 {
                     active_dynamic = Rc::clone(&juliaset);
-                    active_dynamic.borrow_mut().step();
-                    active_dynamic.borrow().visualize_z(&mut canvas);
+                    view = Coloring::Z;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
                 } else if keycode == Keycode::F2 {
                     active_dynamic = Rc::clone(&mandelbrot);
-                    active_dynamic.borrow_mut().step();
-                    active_dynamic.borrow().visualize_d(&mut canvas);
+                    view = Coloring::D;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
                 } else if keycode == Keycode::Num2 {
                     active_dynamic = Rc::clone(&juliaset);
-                    active_dynamic.borrow_mut().step();
-                    active_dynamic.borrow().visualize_d(&mut canvas);
+                    view = Coloring::D;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                } else if keycode == Keycode::F3 {
+                    active_dynamic = Rc::clone(&mandelbrot);
+                    view = Coloring::Smooth;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                } else if keycode == Keycode::Num3 {
+                    active_dynamic = Rc::clone(&juliaset);
+                    view = Coloring::Smooth;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                } else if keycode == Keycode::F4 {
+                    active_dynamic = Rc::clone(&mandelbrot);
+                    view = Coloring::Distance;
+                    track_derivative(&mut workers, &active_dynamic);
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                } else if keycode == Keycode::Num4 {
+                    active_dynamic = Rc::clone(&juliaset);
+                    view = Coloring::Distance;
+                    track_derivative(&mut workers, &active_dynamic);
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                } else if keycode == Keycode::F5 {
+                    active_dynamic = Rc::clone(&mandelbrot);
+                    view = Coloring::Period;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                } else if keycode == Keycode::Num5 {
+                    active_dynamic = Rc::clone(&juliaset);
+                    view = Coloring::Period;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                } else if keycode == Keycode::F6 {
+                    active_dynamic = Rc::clone(&mandelbrot);
+                    view = Coloring::Histogram;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                } else if keycode == Keycode::Num6 {
+                    active_dynamic = Rc::clone(&juliaset);
+                    view = Coloring::Histogram;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+
+                } else if keycode == Keycode::N {
+                    active_dynamic = Rc::clone(&newton);
+                    view = Coloring::Roots;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+
+                } else if keycode == Keycode::J {
+                    preview_enabled = !preview_enabled;
+                    preview = None;
+                    preview_worker = None;
+                    present(&mut canvas, &frame, preview.as_ref(), None);
+
+                } else if keycode == Keycode::F {
+                    if let Some(reformulated) = prompt_formula() {
+                        formula = reformulated;
+                        perturbable = formula.is_quadratic();
+                        tiles = tile_engine(&tiling, &formula)?;
+                        reformulate(&mut workers, &mandelbrot, Box::new(formula.clone().into_fn()), perturbable);
+                        let julia_formula = formula.clone();
+                        let f = move |z, _c| { julia_formula.eval(z, julia_c) };
+                        reformulate(&mut workers, &juliaset, Box::new(f), perturbable);
+                    }
+
+                } else if keycode == Keycode::S {
+                    let session = {
+                        let dynamic = active_dynamic.borrow();
+                        Session {
+                            kind: dynamic.kind,
+                            formula: formula.clone(),
+                            julia: julia_c,
+                            roots: newton.borrow().roots.clone(),
+                            relaxation,
+                            center: dynamic.perturbation.as_ref().map(|perturbation| perturbation.center.clone()),
+                            plane: dynamic.plane,
+                            iterations: target(&workers, &active_dynamic),
+                            coloring: view,
+                            palette: palette.clone(),
+                        }
+                    };
+                    let path = prompt_session_path("save");
+                    match session.save(&path) {
+                        Ok(()) => println!("Saved {}", path.display()),
+                        Err(message) => println!("{}", message),
+                    }
+
+                } else if keycode == Keycode::L {
+                    let path = prompt_session_path("load");
+                    let (width, height) = canvas.output_size()?;
+                    let restored = Session::load(&path)
+                        .and_then(|session| Ok((restore(&mut workers, [&mandelbrot, &juliaset, &newton], &session, (width as usize, height as usize), aspect)?, session)));
+                    match restored {
+                        Ok((restored, session)) => {
+                            active_dynamic = restored;
+                            view = session.coloring;
+                            formula = session.formula;
+                            julia_c = session.julia;
+                            relaxation = session.relaxation;
+                            perturbable = formula.is_quadratic();
+                            tiles = tile_engine(&tiling, &formula)?;
+                            palette = session.palette;
+                            if !palettes.iter().any(|known| known.name == palette.name) {
+                                palettes.push(palette.clone());
+                            }
+                            frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                        }
+                        Err(message) => println!("{}", message),
+                    }
+
+                } else if keycode == Keycode::R {
+                    let clockwise = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    let plane = active_dynamic.borrow().plane;
+                    let plane = plane.rotate(plane.width as i32 / 2, plane.height as i32 / 2, if clockwise { -ROTATION_STEP } else { ROTATION_STEP });
+                    recalculate(&mut workers, &active_dynamic, plane, perturbable, tiled(&tiles, &active_dynamic, &mandelbrot));
+                } else if keycode == Keycode::V {
+                    println!("View {}", active_dynamic.borrow().viewport());
+                    if let Some(viewport) = prompt_viewport() {
+                        go_to(&mut workers, &active_dynamic, &viewport, perturbable);
+                    }
+
+                } else if keycode == Keycode::P {
+                    let k = palettes.iter().position(|known| known.name == palette.name).unwrap_or(0);
+                    let backwards = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    let k = (if backwards { k + palettes.len() - 1 } else { k + 1 }) % palettes.len();
+                    palette = palettes[k].clone();
+                    println!("Palette {}", palette.name);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                } else if keycode == Keycode::I {
+                    let k = ColorSpace::ALL.iter().position(|&space| space == palette.space).unwrap_or(0);
+                    palette.space = ColorSpace::ALL[(k + 1) % ColorSpace::ALL.len()];
+                    println!("Color space {}", palette.space.name());
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                } else if keycode == Keycode::Up || keycode == Keycode::Down {
+                    palette.density *= if keycode == Keycode::Up { DENSITY_STEP } else { 1.0 / DENSITY_STEP };
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                } else if keycode == Keycode::Left || keycode == Keycode::Right {
+                    let step = OFFSET_STEP / palette.density;
+                    palette.offset += if keycode == Keycode::Right { step } else { -step };
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
 
                 } else if keycode == Keycode::SPACE {
-                    active_dynamic.borrow_mut().step();
-                    active_dynamic.borrow().visualize_z(&mut canvas);
+                    view = Coloring::Z;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
                 } else if keycode == Keycode::RETURN {
-                    active_dynamic.borrow_mut().step();
-                    active_dynamic.borrow().visualize_d(&mut canvas);
+                    view = Coloring::D;
+                    step(&workers, &active_dynamic);
+                    frame = show(&mut canvas, &texture_creator, &active_dynamic.borrow(), view, &palette, preview.as_ref())?;
+                }
+
+                if preview.is_some() && !Rc::ptr_eq(&active_dynamic, &mandelbrot) {
+                    /* the preview only belongs to the Mandelbrot set */
+                    preview = None;
+                    preview_worker = None;
+                    present(&mut canvas, &frame, preview.as_ref(), None);
                 }
             }
 
-            Event::MouseButtonDown { x, y, .. } => {
-                active_dynamic.borrow().debug(x.try_into().unwrap(), y.try_into().unwrap());
+            Event::MouseWheel { y: 0, .. } => {}
+
+            Event::MouseWheel { y, mouse_x, mouse_y, .. } => {
+                let rotating = sdl_context.keyboard().mod_state().intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+                let plane = match rotating {
+                    true => active_dynamic.borrow().plane.rotate(mouse_x, mouse_y, ROTATION_STEP * y as Real),
+                    false => {
+                        let zoom = match tiled(&tiles, &active_dynamic, &mandelbrot) {
+                            Some(_) => TILED_WHEEL_ZOOM,
+                            None => WHEEL_ZOOM,
+                        };
+                        active_dynamic.borrow().plane.zoom(mouse_x, mouse_y, zoom.powi(y))
+                    }
+                };
+                recalculate(&mut workers, &active_dynamic, plane, perturbable, tiled(&tiles, &active_dynamic, &mandelbrot));
+            }
+
+            Event::MouseButtonDown { mouse_btn, x, y, .. } => {
+                drag = Some((mouse_btn, x, y));
+            }
+
+            Event::MouseMotion { x, y, .. } => {
+                if let Some((MouseButton::Right, x0, y0)) = drag {
+                    present(&mut canvas, &frame, preview.as_ref(), Some((x0, y0, x, y)));
+                } else if preview_enabled && drag.is_none() && Rc::ptr_eq(&active_dynamic, &mandelbrot) {
+                    /* replacing the worker cancels the preview of the previous c */
+                    let c = absolute_c(&mandelbrot.borrow(), x, y);
+                    let iterations = mandelbrot.borrow().i.max(PREVIEW_ITERATIONS);
+                    preview_worker = Some(Worker::spawn(julia_preview(&formula, c, preview_rect(&canvas)), iterations));
+                }
+            }
+
+            Event::MouseButtonUp { mouse_btn, x, y, .. } => {
+                match drag.take() {
+                    Some((button, x0, y0)) if button == mouse_btn => {
+                        let clicked = (x-x0).abs() < DRAG_THRESHOLD && (y-y0).abs() < DRAG_THRESHOLD;
+                        if clicked {
+                            if mouse_btn == MouseButton::Right {
+                                /* remove the rubber band */
+                                present(&mut canvas, &frame, preview.as_ref(), None);
+                            }
+                            active_dynamic.borrow().debug(x.try_into().unwrap(), y.try_into().unwrap());
+                        } else if mouse_btn == MouseButton::Left {
+                            let plane = active_dynamic.borrow().plane.pan(x-x0, y-y0);
+                            recalculate(&mut workers, &active_dynamic, plane, perturbable, tiled(&tiles, &active_dynamic, &mandelbrot));
+                        } else if mouse_btn == MouseButton::Right {
+                            /* the selected rectangle adapts to the window like any other view */
+                            let plane = active_dynamic.borrow().plane;
+                            let plane = plane.select(x0, y0, x, y).resize(plane.width, plane.height, aspect);
+                            recalculate(&mut workers, &active_dynamic, plane, perturbable, tiled(&tiles, &active_dynamic, &mandelbrot));
+                        }
+                    }
+                    _ => {}
+                }
             }
 
             Event::Window { timestamp: _, window_id: _, win_event } => {
@@ -122,31 +798,19 @@ fn main() -> Result<(), String> {
                         canvas.clear();
                         canvas.present();
 
+                        let (width, height) = (w as usize, h as usize);
                         {
-                            let old_iter = juliaset.borrow().i;
-                            let plane = Plane {re_min: -2.0, re_max: 0.55, im_min: -1.2, im_max: 1.2, width: w as usize, height: h as usize};
-                            let f = juliaset.borrow().f.clone();
-                            juliaset = Rc::new(RefCell::new(Juliaset::new(plane, f)));
-                            print!("Recalculating {} iterations of Julia set…", old_iter);
-                            let _ = stdout().flush();
-                            let mut j = juliaset.borrow_mut();
-                            for _ in 0..old_iter { j.step(); }
-                            println!(" ✓");
+                            let plane = juliaset.borrow().plane.resize(width, height, aspect);
+                            recalculate(&mut workers, &juliaset, plane, perturbable, None);
                         }
                         {
-                            let old_iter = mandelbrot.borrow().i;
-                            let plane = Plane {re_min: -2.0, re_max: 0.55, im_min: -1.2, im_max: 1.2, width: w as usize, height: h as usize};
-                            let f = mandelbrot.borrow().f.clone();
-                            mandelbrot = Rc::new(RefCell::new(Mandelbrot::new(plane, f)));
-                            print!("Recalculating {} iterations of Mandelbrot set…", old_iter);
-                            let _ = stdout().flush();
-                            let mut m = mandelbrot.borrow_mut();
-                            for _ in 0..old_iter { m.step(); }
-                            println!(" ✓");
+                            let plane = mandelbrot.borrow().plane.resize(width, height, aspect);
+                            recalculate(&mut workers, &mandelbrot, plane, perturbable, tiles.as_ref());
+                        }
+                        {
+                            let plane = newton.borrow().plane.resize(width, height, aspect);
+                            recalculate(&mut workers, &newton, plane, perturbable, None);
                         }
-
-                        active_dynamic = Rc::clone(&mandelbrot);
-                        active_dynamic.borrow().visualize_z(&mut canvas);
                     }
                     _ => {}
                 }
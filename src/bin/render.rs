@@ -0,0 +1,214 @@
+use fractals::cli::{parse_numbers, parse_size};
+use fractals::coloring::{color_space::ColorSpace, palette::Palette};
+use fractals::holomorphic::checkpoint;
+use fractals::holomorphic::dynamic::DynamicKind;
+use fractals::holomorphic::formula::Formula;
+use fractals::holomorphic::plane::{Aspect, Plane};
+use fractals::holomorphic::session::Session;
+use fractals::holomorphic::simulation::Simulation;
+use fractals::holomorphic::supersampling::{Sampling, Supersample};
+use fractals::holomorphic::viewport::Viewport;
+use fractals::holomorphic::visualize::{Coloring, Visualize};
+use num::complex::Complex;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+
+const USAGE: &str = "Renders a fractal into an image file without opening a window
+
+Usage: render [OPTIONS]
+
+Options:
+  --session FILE                          start from a saved session, the following options override it
+  --viewport RE_MIN,RE_MAX,IM_MIN,IM_MAX  clipping of the complex plane [default: -2.0,0.55,-1.2,1.2]
+  --view RE,IM,SCALE[,ROTATION]           the view by its center, height and rotation in degrees, where RE and IM
+                                          may have any number of digits, e.g. as printed by [V] in mandelbrot
+  --size WIDTHxHEIGHT                     resolution in pixels [default: 800x600]
+  --aspect fit|fill|stretch               adapting the viewport to the resolution: with square pixels showing all of
+                                          it or covering the image with it, or stretched [default: fit]
+  --iterations N                          number of iterations [default: 100]
+  --coloring z|d|smooth|histogram|distance|roots|period
+                                          color the state z, the iteration of divergence, its smooth variant, the
+                                          smooth variant equalized over the image, the distance to the set, the basins
+                                          of the Newton method or the period of interior points [default: z]
+  --palette NAME|FILE                     colors of the iterations: default, ultra, fire, gray or a .ggr, .map, .csv
+                                          or .json file [default: default]
+  --color-space srgb|linear|oklab|oklch   blending of the palette and domain coloring of z [default: of the palette]
+  --formula FORMULA                       the iterated function f(z, c), e.g. \"z^3 + c\" or \"sin(z)*c\" [default: z^2 + c]
+  --julia RE,IM                           render the Julia set of the formula for this c instead of the Mandelbrot set
+  --newton ROOTS                          render the Newton method for the polynomial with comma separated roots,
+                                          e.g. \"1, -0.5+0.866i, -0.5-0.866i\"
+  --relaxation RE,IM                      relaxation of the Newton method [default: 1,0]
+  --samples N                             anti-aliasing with N×N samples per pixel [default: 1]
+  --sampling grid|adaptive                placement of the samples in a regular grid for all pixels or only where
+                                          neighbouring pixels differ strongly [default: grid]
+  --output FILE                           .png, .ppm or .gif file [default: fractal.png]
+  --checkpoint FILE                       save the simulation periodically to FILE and resume from it, if it exists
+  --checkpoint-interval SECONDS           time between checkpoints [default: 600]
+  --help                                  print this help";
+
+struct Options {
+    session: Session,
+    output: PathBuf,
+    checkpoint: Option<PathBuf>,
+    checkpoint_interval: Duration,
+    samples: usize,     // per axis of a pixel
+    sampling: Sampling,
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        session: Session::default(),
+        output: PathBuf::from("fractal.png"),
+        checkpoint: None,
+        checkpoint_interval: Duration::from_secs(600),
+        samples: 1,
+        sampling: Sampling::Grid,
+    };
+    let session = &mut options.session;
+    /* applied after all options, so it overrides the space of any palette */
+    let mut color_space = None;
+    /* applied after all options, once the size and formula are known */
+    let mut viewport = None;
+    let mut aspect = Aspect::Fit;
+
+    while let Some(arg) = args.next() {
+        if arg == "--help" {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+        let value = args.next().ok_or(format!("{} requires a value", arg))?;
+        match arg.as_str() {
+            "--session" => {
+                *session = Session::load(value.as_ref())?;
+            }
+            "--viewport" => {
+                let v = parse_numbers(&value, 4)?;
+                /* absolute coordinates */
+                session.center = None;
+                session.plane = Plane {re_min: v[0], re_max: v[1], im_min: v[2], im_max: v[3], ..session.plane};
+            }
+            "--view" => {
+                viewport = Some(Viewport::parse(&value)?);
+            }
+            "--size" => {
+                let (width, height) = parse_size(&value)?;
+                session.plane = Plane {width, height, ..session.plane};
+            }
+            "--aspect" => {
+                aspect = Aspect::parse(&value)?;
+            }
+            "--iterations" => {
+                session.iterations = value.parse().map_err(|e| format!("'{}': {}", value, e))?;
+            }
+            "--coloring" => {
+                session.coloring = Coloring::parse(&value)?;
+            }
+            "--palette" => {
+                session.palette = Palette::find(&value)?;
+            }
+            "--color-space" => {
+                color_space = Some(ColorSpace::parse(&value)?);
+            }
+            "--formula" => {
+                session.formula = Formula::parse(&value)?;
+            }
+            "--julia" => {
+                let v = parse_numbers(&value, 2)?;
+                session.kind = DynamicKind::Juliaset;
+                session.julia = Complex::new(v[0], v[1]);
+            }
+            "--newton" => {
+                let roots = value.split(',')
+                    .map(|root| Formula::<f64>::parse(root).map(|root| root.eval(Complex::new(0.0, 0.0), Complex::new(0.0, 0.0))))
+                    .collect::<Result<Vec<_>, _>>()?;
+                session.kind = DynamicKind::Newton;
+                session.roots = roots;
+            }
+            "--relaxation" => {
+                let v = parse_numbers(&value, 2)?;
+                session.relaxation = Complex::new(v[0], v[1]);
+            }
+            "--samples" => {
+                options.samples = value.parse().map_err(|e| format!("'{}': {}", value, e))?;
+            }
+            "--sampling" => {
+                options.sampling = Sampling::parse(&value)?;
+            }
+            "--output" => {
+                options.output = PathBuf::from(value);
+            }
+            "--checkpoint" => {
+                options.checkpoint = Some(PathBuf::from(value));
+            }
+            "--checkpoint-interval" => {
+                let seconds = value.parse::<f64>().map_err(|e| format!("'{}': {}", value, e))?;
+                options.checkpoint_interval = Duration::try_from_secs_f64(seconds).map_err(|e| format!("'{}': {}", value, e))?;
+            }
+            _ => return Err(format!("unknown option {}\n\n{}", arg, USAGE)),
+        }
+    }
+    if let Some(space) = color_space {
+        session.palette.space = space;
+    }
+    if let Some(viewport) = viewport {
+        session.set_viewport(&viewport);
+    }
+    session.plane = session.plane.resize(session.plane.width, session.plane.height, aspect);
+    Ok(options)
+}
+
+fn main() -> Result<(), String> {
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    let session = options.session;
+    let mut dynamic = session.dynamic()?;
+
+    if let Some(path) = options.checkpoint.as_ref().filter(|path| path.exists()) {
+        let (resumed_session, resumed) = checkpoint::load(path)?;
+        /* the iterations may be extended and the coloring changed, but the fractal must be the same */
+        let fractal = |session: &Session| Session {iterations: 0, coloring: Coloring::Z, palette: Palette::default(), ..session.clone()}.to_string();
+        if fractal(&resumed_session) != fractal(&session.with_dynamic(&dynamic)) {
+            return Err(format!("{}: the checkpoint belongs to another fractal", path.display()));
+        }
+        if dynamic.derivative && !resumed.derivative {
+            return Err(format!("{}: the checkpoint didn't track the derivative, which the distance coloring needs", path.display()));
+        }
+        dynamic = resumed;
+        println!("Resumed {} at iteration {}", path.display(), dynamic.i);
+    }
+
+    let start = Instant::now();
+    let mut saved = Instant::now();
+    let iterations = session.iterations - dynamic.i;
+    while dynamic.i < session.iterations {
+        dynamic.step();
+        if let Some(path) = &options.checkpoint {
+            if saved.elapsed() >= options.checkpoint_interval || dynamic.i == session.iterations {
+                checkpoint::save(path, &session, &dynamic)?;
+                println!("Saved checkpoint {} at iteration {}", path.display(), dynamic.i);
+                saved = Instant::now();
+            }
+        }
+    }
+    println!("Calculated {} iterations in {}ms", iterations.max(0), start.elapsed().as_millis());
+
+    let image = if options.samples > 1 {
+        let start = Instant::now();
+        let image = dynamic.supersample(options.sampling, options.samples, session.coloring, &session.palette);
+        println!("Supersampled {}×{} ({}) in {}ms", options.samples, options.samples, options.sampling.name(), start.elapsed().as_millis());
+        image
+    } else {
+        dynamic.visualize(session.coloring, &session.palette)
+    };
+    image.save(&options.output)?;
+    println!("Saved {}", options.output.display());
+
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use fractals::cli::{parse_numbers, parse_size};
+use fractals::coloring::palette::Palette;
+use fractals::holomorphic::dynamic::DynamicKind;
+use fractals::holomorphic::formula::Formula;
+use fractals::holomorphic::plane::{Aspect, Plane};
+use fractals::holomorphic::session::Session;
+use fractals::holomorphic::viewport::Viewport;
+use fractals::holomorphic::visualize::Coloring;
+use fractals::holomorphic::zoom::Zoom;
+use fractals::image::gif::GifWriter;
+use num::complex::Complex;
+use std::{fs, io::BufWriter, path::PathBuf};
+use std::time::{Duration, Instant};
+
+
+const USAGE: &str = "Renders a zoom from a start view into a target view as PNG frames and an animated GIF
+
+Usage: zoom [OPTIONS]
+
+Options:
+  --session FILE                          the target view, the following options override it
+  --viewport RE_MIN,RE_MAX,IM_MIN,IM_MAX  clipping of the complex plane of the target view
+  --view RE,IM,SCALE                      the target view by its center and height, e.g. as printed by [V] in mandelbrot
+  --start RE_MIN,RE_MAX,IM_MIN,IM_MAX     clipping of the complex plane of the first frame [default: -2.0,0.55,-1.2,1.2]
+  --size WIDTHxHEIGHT                     resolution of the frames in pixels [default: 800x600]
+  --aspect fit|fill|stretch               adapting both views to the resolution: with square pixels showing all of
+                                          them or covering the frames with them, or stretched [default: fit]
+  --iterations N                          number of iterations of the last frame [default: 100]
+  --start-iterations N                    number of iterations of the first frame [default: 100]
+  --frames N                              number of frames [default: 100]
+  --fps N                                 frames per second of the GIF [default: 25]
+  --coloring z|d|smooth|histogram|distance|roots|period
+                                          the quantity, which is colored [default: z]
+  --palette NAME|FILE                     colors of the iterations: default, ultra, fire, gray or a .ggr, .map, .csv
+                                          or .json file [default: default]
+  --formula FORMULA                       the iterated function f(z, c) [default: z^2 + c]
+  --julia RE,IM                           zoom into the Julia set of the formula for this c
+  --output DIRECTORY                      of the frames frame_0000.png, frame_0001.png, … [default: zoom]
+  --gif FILE                              the animation [default: zoom.gif]
+  --help                                  print this help";
+
+struct Options {
+    session: Session,
+    start: Plane<f64>,
+    start_iterations: i32,
+    frames: usize,
+    fps: u32,
+    output: PathBuf,
+    gif: PathBuf,
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let session = Session::default();
+    let mut options = Options {
+        start: session.plane,
+        session,
+        start_iterations: 100,
+        frames: 100,
+        fps: 25,
+        output: PathBuf::from("zoom"),
+        gif: PathBuf::from("zoom.gif"),
+    };
+    let session = &mut options.session;
+    /* applied after all options, once the size and formula are known */
+    let mut viewport = None;
+    let mut aspect = Aspect::Fit;
+
+    while let Some(arg) = args.next() {
+        if arg == "--help" {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+        let value = args.next().ok_or(format!("{} requires a value", arg))?;
+        let parse_count = |value: &str| value.parse::<usize>().map_err(|e| format!("'{}': {}", value, e));
+        match arg.as_str() {
+            "--session" => {
+                *session = Session::load(value.as_ref())?;
+            }
+            "--viewport" => {
+                let v = parse_numbers(&value, 4)?;
+                /* absolute coordinates */
+                session.center = None;
+                session.plane = Plane {re_min: v[0], re_max: v[1], im_min: v[2], im_max: v[3], ..session.plane};
+            }
+            "--view" => {
+                viewport = Some(Viewport::parse(&value)?);
+            }
+            "--start" => {
+                let v = parse_numbers(&value, 4)?;
+                options.start = Plane {re_min: v[0], re_max: v[1], im_min: v[2], im_max: v[3], ..options.start};
+            }
+            "--size" => {
+                let (width, height) = parse_size(&value)?;
+                session.plane = Plane {width, height, ..session.plane};
+            }
+            "--aspect" => {
+                aspect = Aspect::parse(&value)?;
+            }
+            "--iterations" => {
+                session.iterations = value.parse().map_err(|e| format!("'{}': {}", value, e))?;
+            }
+            "--start-iterations" => {
+                options.start_iterations = value.parse().map_err(|e| format!("'{}': {}", value, e))?;
+            }
+            "--frames" => {
+                options.frames = parse_count(&value)?.max(1);
+            }
+            "--fps" => {
+                options.fps = parse_count(&value)?.max(1) as u32;
+            }
+            "--coloring" => {
+                session.coloring = Coloring::parse(&value)?;
+            }
+            "--palette" => {
+                session.palette = Palette::find(&value)?;
+            }
+            "--formula" => {
+                session.formula = Formula::parse(&value)?;
+            }
+            "--julia" => {
+                let v = parse_numbers(&value, 2)?;
+                session.kind = DynamicKind::Juliaset;
+                session.julia = Complex::new(v[0], v[1]);
+            }
+            "--output" => {
+                options.output = PathBuf::from(value);
+            }
+            "--gif" => {
+                options.gif = PathBuf::from(value);
+            }
+            _ => return Err(format!("unknown option {}\n\n{}", arg, USAGE)),
+        }
+    }
+    if let Some(viewport) = viewport {
+        session.set_viewport(&viewport);
+    }
+    let (width, height) = (session.plane.width, session.plane.height);
+    session.plane = session.plane.resize(width, height, aspect);
+    options.start = options.start.resize(width, height, aspect);
+    Ok(options)
+}
+
+fn main() -> Result<(), String> {
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    let (width, height) = (options.session.plane.width, options.session.plane.height);
+    let mut zoom = Zoom::new(options.session, options.start, options.start_iterations, options.frames);
+    fs::create_dir_all(&options.output).map_err(|e| format!("{}: {}", options.output.display(), e))?;
+    let file = fs::File::create(&options.gif).map_err(|e| format!("{}: {}", options.gif.display(), e))?;
+    let mut gif = GifWriter::new(BufWriter::new(file), width, height, Duration::from_secs(1) / options.fps)?;
+
+    let start = Instant::now();
+    for k in 0..options.frames {
+        let frame_start = Instant::now();
+        let (image, simulated) = zoom.frame(k)?;
+        let path = options.output.join(format!("frame_{:04}.png", k));
+        image.save(&path)?;
+        gif.write_frame(&image)?;
+        let how = if simulated { "simulated" } else { "resampled" };
+        println!("Saved {} ({}) in {}ms", path.display(), how, frame_start.elapsed().as_millis());
+    }
+    gif.finish()?;
+    println!("Saved {} with {} frames in {}s", options.gif.display(), options.frames, start.elapsed().as_secs());
+
+    Ok(())
+}
@@ -0,0 +1,21 @@
+/** exactly n comma separated numbers like "-2.0,0.5,-1.2,1.2" **/
+pub fn parse_numbers(value: &str, n: usize) -> Result<Vec<f64>, String> {
+    let numbers = value.split(',')
+        .map(|number| number.trim().parse::<f64>().map_err(|e| format!("'{}': {}", number, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if numbers.len() != n {
+        return Err(format!("'{}': expected {} comma separated numbers", value, n));
+    }
+    Ok(numbers)
+}
+
+/** a resolution like "800x600", which must not be empty **/
+pub fn parse_size(value: &str) -> Result<(usize, usize), String> {
+    let (width, height) = value.split_once('x').ok_or(format!("'{}': expected WIDTHxHEIGHT", value))?;
+    let width = width.parse::<usize>().map_err(|e| format!("'{}': {}", width, e))?;
+    let height = height.parse::<usize>().map_err(|e| format!("'{}': {}", height, e))?;
+    if width == 0 || height == 0 {
+        return Err(format!("'{}': the image must not be empty", value));
+    }
+    Ok((width, height))
+}
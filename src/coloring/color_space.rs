@@ -0,0 +1,132 @@
+use std::f32::consts::PI;
+use super::{Color, Rgb};
+
+
+/** The space, in which colors are blended
+ *  - sRGB is what 8 bit images store, blending it darkens the midpoints
+ *  - linear light blends like physical light
+ *  - OKLab (https://bottosson.github.io/posts/oklab/) is perceptually uniform: equal steps look equally large
+ *  - OKLCH is OKLab in polar coordinates, blending the hue along the shorter arc
+ **/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+    Oklab,
+    Oklch,
+}
+
+impl ColorSpace {
+    pub const ALL: [ColorSpace; 4] = [ColorSpace::Srgb, ColorSpace::Linear, ColorSpace::Oklab, ColorSpace::Oklch];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::Linear => "linear",
+            ColorSpace::Oklab => "oklab",
+            ColorSpace::Oklch => "oklch",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL.into_iter().find(|space| space.name() == name)
+            .ok_or(format!("'{}': unknown color space, use srgb, linear, oklab or oklch", name))
+    }
+
+    /** the sRGB color at the fraction f between the sRGB colors a and b **/
+    pub fn mix(self, a: Color, b: Color, f: f32) -> Color {
+        let lerp = |a: Color, b: Color| [0, 1, 2].map(|k| a[k] + (b[k] - a[k]) * f);
+        match self {
+            ColorSpace::Srgb => lerp(a, b),
+            ColorSpace::Linear => linear_to_srgb(lerp(srgb_to_linear(a), srgb_to_linear(b))),
+            ColorSpace::Oklab => oklab_to_srgb(lerp(srgb_to_oklab(a), srgb_to_oklab(b))),
+            ColorSpace::Oklch => {
+                let ([l_a, c_a, h_a], [l_b, c_b, h_b]) = (oklab_to_oklch(srgb_to_oklab(a)), oklab_to_oklch(srgb_to_oklab(b)));
+                /* grays have no hue, so they take the hue of the other color */
+                let (h_a, h_b) = match (c_a < ACHROMATIC, c_b < ACHROMATIC) {
+                    (true, false) => (h_b, h_b),
+                    (false, true) => (h_a, h_a),
+                    _ => (h_a, h_b),
+                };
+                let dh = (h_b - h_a + PI).rem_euclid(2.0 * PI) - PI;
+                oklab_to_srgb(oklch_to_oklab([l_a + (l_b - l_a) * f, c_a + (c_b - c_a) * f, h_a + dh * f]))
+            }
+        }
+    }
+}
+
+/** Below this chroma, the hue of an OKLCH color is meaningless **/
+const ACHROMATIC: f32 = 1.0e-4;
+
+
+/** removes the gamma of sRGB **/
+pub fn srgb_to_linear(color: Color) -> Color {
+    color.map(|c| if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) })
+}
+
+/** applies the gamma of sRGB **/
+pub fn linear_to_srgb(color: Color) -> Color {
+    color.map(|c| if c <= 0.0031308 { 12.92 * c } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 })
+}
+
+/** linear sRGB -> (lightness, green/red, blue/yellow) **/
+pub fn linear_to_oklab([r, g, b]: Color) -> Color {
+    let l = (0.41222147 * r + 0.53633254 * g + 0.051445993 * b).cbrt();
+    let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+    let s = (0.08830246 * r + 0.28171884 * g + 0.6299787 * b).cbrt();
+    [
+        0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+        1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+        0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+    ]
+}
+
+pub fn oklab_to_linear([lightness, a, b]: Color) -> Color {
+    let l = (lightness + 0.39633778 * a + 0.21580376 * b).powi(3);
+    let m = (lightness - 0.105561346 * a - 0.06385417 * b).powi(3);
+    let s = (lightness - 0.08948418 * a - 1.2914855 * b).powi(3);
+    [
+        4.0767417 * l - 3.3077116 * m + 0.23096993 * s,
+        -1.268438 * l + 2.6097574 * m - 0.3413194 * s,
+        -0.0041960863 * l - 0.7034186 * m + 1.7076147 * s,
+    ]
+}
+
+/** (lightness, chroma, hue in radians) **/
+pub fn oklab_to_oklch([l, a, b]: Color) -> Color {
+    [l, a.hypot(b), b.atan2(a)]
+}
+
+pub fn oklch_to_oklab([l, c, h]: Color) -> Color {
+    [l, c * h.cos(), c * h.sin()]
+}
+
+pub fn srgb_to_oklab(color: Color) -> Color {
+    linear_to_oklab(srgb_to_linear(color))
+}
+
+pub fn oklab_to_srgb(lab: Color) -> Color {
+    linear_to_srgb(oklab_to_linear(lab))
+}
+
+/** the OKLCH color as sRGB, with the chroma reduced until it fits into the gamut (keeping lightness and hue) **/
+pub fn oklch_to_srgb_in_gamut([l, c, h]: Color) -> Color {
+    let in_gamut = |c: f32| oklab_to_linear(oklch_to_oklab([l, c, h])).iter().all(|&x| (-1.0e-4..=1.0 + 1.0e-4).contains(&x));
+    let chroma = if in_gamut(c) {
+        c
+    } else {
+        /* bisection of the largest chroma in gamut */
+        let (mut low, mut high) = (0.0, c);
+        for _ in 0..12 {
+            let mid = (low + high) / 2.0;
+            if in_gamut(mid) { low = mid } else { high = mid }
+        }
+        low
+    };
+    oklab_to_srgb(oklch_to_oklab([l, chroma, h]))
+}
+
+/** sRGB components in [0, 1] (clipped) to bytes **/
+pub fn encode(color: Color) -> Rgb {
+    color.map(|c| (255.0 * c.clamp(0.0, 1.0) + 0.5) as u8)
+}
@@ -1,9 +1,10 @@
 use std::f32::consts::PI;
 
 use num::complex::Complex32;
-use sdl2::pixels::Color;
+use super::color_space::{encode, linear_to_srgb, oklch_to_srgb_in_gamut, srgb_to_linear, ColorSpace};
 use super::helper::*;
-use super::hsl::hsl_to_cartesian;
+use super::hsl::{hsl_to_cartesian, hsl_to_rgb};
+use super::Rgb;
 
 /** Colors complex number z
  *  - real part of z will be colored blueish
@@ -11,12 +12,87 @@ use super::hsl::hsl_to_cartesian;
  *  - positive real and imaginary parts are distinguished from their negative counterpart by an
  *  additional red component
  *  - the lightness increases from origin (black) till a distance of radius
+ *
+ *  In linear light, these colors are taken as intensities of light instead of sRGB values, so the lightness rises
+ *  physically linear (and the colors appear brighter). In the perceptual color spaces (OKLab, OKLCH), the argument
+ *  of z is the OKLCH hue instead, so all arguments appear equally bright.
  **/
-pub fn domain_coloring(z: Complex32, radius: f32, s: f32) -> Color {
+pub fn domain_coloring(z: Complex32, radius: f32, s: f32, space: ColorSpace) -> Rgb {
     let (r, theta) = z.to_polar();
 
-    let h = (theta+PI)/(2.0*PI);
-    let l = 0.3 + 0.3*norm(r, 0.0, radius);
+    match space {
+        ColorSpace::Srgb => {
+            let h = (theta+PI)/(2.0*PI);
+            let l = 0.3 + 0.3*norm(r, 0.0, radius);
+            hsl_to_cartesian(h, s, l)
+        }
+        ColorSpace::Linear => {
+            let color = domain_coloring(z, radius, s, ColorSpace::Srgb);
+            encode(linear_to_srgb(color.map(|c| c as f32 / 255.0)))
+        }
+        ColorSpace::Oklab | ColorSpace::Oklch => {
+            let l = 0.35 + 0.4*norm(r, 0.0, radius);
+            encode(oklch_to_srgb_in_gamut([l, 0.2*s, theta]))
+        }
+    }
+}
+
+
+/** Overlays of enhanced phase portraits (like in Wegert's "Visual Complex Functions")
+ *  - modulus: brightness rising from one contour line of log2|w| to the next, so zeros and poles are surrounded
+ *    by rings and |w| doubles from ring to ring
+ *  - phase: brightness rising between isochromatic lines, which divide the argument into PHASE_SECTORS
+ *  - grid: the lines of constant Re w and Im w, whose images in the z-plane are orthogonal where f is conformal
+ **/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Enhancement {
+    pub modulus: bool,
+    pub phase: bool,
+    pub grid: bool,
+}
+
+/** Isochromatic lines per turn of the argument **/
+pub const PHASE_SECTORS: f32 = 12.0;
+
+/** Distance of the lines of the conformal grid in the w-plane **/
+pub const GRID_SPACING: f32 = 0.25;
+
+/** Colors a value w = f(z) by its argument (the hue), enhanced by overlays
+ *  - dw = |f'(z)| · pixel size is the size of a pixel in the w-plane, which keeps grid lines 1 pixel wide
+ *  - zeros, poles and other non-finite values are black
+ **/
+pub fn phase_portrait(w: Complex32, dw: f32, enhancement: Enhancement, space: ColorSpace) -> Rgb {
+    let (r, theta) = w.to_polar();
+    if !r.is_finite() || r == 0.0 || !theta.is_finite() {
+        return [0, 0, 0];
+    }
+    let sawtooth = |x: f32| 0.7 + 0.3 * x.rem_euclid(1.0);
+
+    let mut brightness = 1.0;
+    if enhancement.modulus {
+        brightness *= sawtooth(r.log2());
+    }
+    if enhancement.phase {
+        brightness *= sawtooth(PHASE_SECTORS * (theta + PI) / (2.0 * PI));
+    }
+    if enhancement.grid && dw.is_finite() && dw > 0.0 {
+        /* the distance to the next line in pixels, lines closer than 4 pixels fade out */
+        let pixels = |x: f32| (x / GRID_SPACING - (x / GRID_SPACING).round()).abs() * GRID_SPACING / dw;
+        let visibility = norm(GRID_SPACING / dw, 4.0, 8.0);
+        let line = |x: f32| 1.0 - visibility * (1.0 - norm(pixels(x), 0.5, 1.5));
+        brightness *= 0.35 + 0.65 * line(w.re).min(line(w.im));
+    }
 
-    hsl_to_cartesian(h, s, l)
+    match space {
+        ColorSpace::Srgb => {
+            /* positive reals are red, like the OKLCH hue 0 */
+            hsl_to_rgb((theta / (2.0*PI)).rem_euclid(1.0), 1.0, 0.5).map(|c| (c as f32 * brightness) as u8)
+        }
+        ColorSpace::Linear => {
+            /* the overlays dim the light, rather than the sRGB values */
+            let color = srgb_to_linear(hsl_to_rgb((theta / (2.0*PI)).rem_euclid(1.0), 1.0, 0.5).map(|c| c as f32 / 255.0));
+            encode(linear_to_srgb(color.map(|c| c * brightness)))
+        }
+        ColorSpace::Oklab | ColorSpace::Oklch => encode(oklch_to_srgb_in_gamut([0.75 * brightness, 0.15, theta])),
+    }
 }
@@ -1,7 +1,7 @@
-use sdl2::pixels::Color;
+use super::Rgb;
 
 /** like https://en.wikipedia.org/wiki/HSL_and_HSV#HSL_to_RGB_alternative **/
-pub fn hsl_to_rgb(h: f32, s: f32, l: f32) -> Color {
+pub fn hsl_to_rgb(h: f32, s: f32, l: f32) -> Rgb {
     let a = s * l.min(1.0-l);
 
     let f = |n: f32| {
@@ -10,10 +10,10 @@ pub fn hsl_to_rgb(h: f32, s: f32, l: f32) -> Color {
         (255.0 * r) as u8
     };
 
-    Color::RGB(f(0.0), f(8.0), f(4.0))
+    [f(0.0), f(8.0), f(4.0)]
 }
 
-pub fn hsl_to_cartesian(h: f32, s: f32, l: f32) -> Color {
+pub fn hsl_to_cartesian(h: f32, s: f32, l: f32) -> Rgb {
     let a = s * l.min(1.0-l);
 
     let f = |n: f32| {
@@ -22,5 +22,5 @@ pub fn hsl_to_cartesian(h: f32, s: f32, l: f32) -> Color {
         (127.0 * r) as u8
     };
 
-    Color::RGB(f(6.0)+f(9.0), f(3.0)+f(9.0), f(0.0)+f(6.0), )
+    [f(6.0)+f(9.0), f(3.0)+f(9.0), f(0.0)+f(6.0)]
 }
@@ -1,4 +1,13 @@
+pub mod color_space;
 pub mod helper;
 pub mod hsl;
 pub mod domain_coloring;
 pub mod gradient;
+pub mod palette;
+pub mod palette_file;
+
+/** 8 bit per channel color, independent of any graphics backend **/
+pub type Rgb = [u8; 3];
+
+/** components in [0, 1], sRGB unless told otherwise **/
+pub type Color = [f32; 3];
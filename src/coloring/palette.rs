@@ -0,0 +1,170 @@
+use std::path::Path;
+use super::{color_space::{encode, ColorSpace}, Color, Rgb};
+
+
+/** Positions per unit of the colored value for palettes, which don't define it: a cycle every 32 iterations **/
+pub const DENSITY: f32 = 1.0 / 32.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stop {
+    pub position: f32,  // in [0, 1]
+    pub color: Color,
+}
+
+/** How colors are blended between neighbouring stops **/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,    // the color of the previous stop, without blending
+    Linear,
+    Smooth,  // eased by smoothstep, which avoids kinks at the stops
+}
+
+/** How positions outside of [0, 1] are mapped into the palette **/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Extend {
+    Clamp,   // the color of the first or last stop
+    Repeat,  // the last stop is blended into the first one
+    Mirror,  // back and forth
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 3] = [Interpolation::Step, Interpolation::Linear, Interpolation::Smooth];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Interpolation::Step => "step",
+            Interpolation::Linear => "linear",
+            Interpolation::Smooth => "smooth",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL.into_iter().find(|interpolation| interpolation.name() == name)
+            .ok_or(format!("'{}': unknown interpolation, use step, linear or smooth", name))
+    }
+}
+
+impl Extend {
+    pub const ALL: [Extend; 3] = [Extend::Clamp, Extend::Repeat, Extend::Mirror];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Extend::Clamp => "clamp",
+            Extend::Repeat => "repeat",
+            Extend::Mirror => "mirror",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL.into_iter().find(|extend| extend.name() == name)
+            .ok_or(format!("'{}': unknown extend, use clamp, repeat or mirror", name))
+    }
+}
+
+
+/** Maps continuous values (like smooth iteration counts) to colors
+ *  > position = (value - offset) · density
+ *
+ *  The position is extended into [0, 1] and colored by blending the stops around it.
+ **/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub name: String,     // of a builtin palette or the path of a palette file
+    pub stops: Vec<Stop>, // sorted by position
+    pub interpolation: Interpolation,
+    pub space: ColorSpace,
+    pub extend: Extend,
+    pub offset: f32,      // the value at position 0
+    pub density: f32,     // positions per unit of the value, 1/density values make a cycle
+}
+
+impl Palette {
+    /** a repeating palette, linearly interpolated in OKLab, of at least one stop with positions in [0, 1] **/
+    pub fn new(name: &str, mut stops: Vec<Stop>) -> Result<Self, String> {
+        if stops.is_empty() {
+            return Err("a palette needs at least one color".to_string());
+        }
+        if let Some(stop) = stops.iter().find(|stop| !(0.0..=1.0).contains(&stop.position)) {
+            return Err(format!("the position {} is not in [0, 1]", stop.position));
+        }
+        /* stable, so stops at the same position remain a discontinuity */
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        Ok(Self {name: name.to_string(), stops, interpolation: Interpolation::Linear, space: ColorSpace::Oklab, extend: Extend::Repeat, offset: 0.0, density: DENSITY})
+    }
+
+    /** the color of a value, non-finite values are black **/
+    pub fn color(&self, value: f32) -> Rgb {
+        self.color_at((value - self.offset) * self.density)
+    }
+
+    /** the color at a position, which is extended into [0, 1] **/
+    pub fn color_at(&self, u: f32) -> Rgb {
+        if !u.is_finite() {
+            return [0, 0, 0];
+        }
+        let t = match self.extend {
+            Extend::Clamp => u.clamp(0.0, 1.0),
+            Extend::Repeat => u.rem_euclid(1.0),
+            Extend::Mirror => 1.0 - (u.rem_euclid(2.0) - 1.0).abs(),
+        };
+        encode(self.blend(t))
+    }
+
+    fn blend(&self, t: f32) -> Color {
+        let (first, last) = (self.stops[0], self.stops[self.stops.len() - 1]);
+        let next = self.stops.partition_point(|stop| stop.position <= t);
+        let (a, b) = match (next, self.extend) {
+            (0, Extend::Repeat) => (Stop {position: last.position - 1.0, ..last}, first),
+            (0, _) => return first.color,
+            (n, Extend::Repeat) if n == self.stops.len() => (last, Stop {position: first.position + 1.0, ..first}),
+            (n, _) if n == self.stops.len() => return last.color,
+            (n, _) => (self.stops[n - 1], self.stops[n]),
+        };
+        let f = if b.position > a.position { (t - a.position) / (b.position - a.position) } else { 0.0 };
+        let f = match self.interpolation {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => f,
+            Interpolation::Smooth => f * f * (3.0 - 2.0 * f),
+        };
+        self.space.mix(a.color, b.color, f)
+    }
+
+    /** the builtin palettes, the first one is the default **/
+    pub fn builtins() -> Vec<Palette> {
+        let palette = |name: &str, stops: &[(f32, Rgb)]| {
+            let stops = stops.iter().map(|&(position, color)| Stop {position, color: color.map(|c| c as f32 / 255.0)}).collect();
+            Palette::new(name, stops).expect("valid builtin palette")
+        };
+        /* black -> red -> green -> blue -> black for the values 10..60, like gradient_rgb (in sRGB) */
+        let channel = |peak: f32, width: f32| move |s: f32| ((width - (s - peak).abs()) / 30.0).max(0.0);
+        let (r, g, b) = (channel(20.0, 10.0), channel(30.0, 15.0), channel(40.0, 20.0));
+        let stops = [10.0, 15.0, 20.0, 30.0, 40.0, 45.0, 60.0].iter()
+            .map(|&s| Stop {position: (s - 10.0) / 50.0, color: [r(s), g(s), b(s)]})
+            .collect();
+        let default = Palette {space: ColorSpace::Srgb, extend: Extend::Clamp, offset: 10.0, density: 1.0 / 50.0, ..Palette::new("default", stops).expect("valid builtin palette")};
+
+        vec![
+            default,
+            palette("ultra", &[(0.0, [0, 7, 100]), (0.16, [32, 107, 203]), (0.42, [237, 255, 255]), (0.6425, [255, 170, 0]), (0.8575, [0, 2, 0])]),
+            palette("fire", &[(0.0, [0, 0, 0]), (0.3, [200, 20, 0]), (0.6, [255, 200, 0]), (0.8, [255, 255, 255])]),
+            Palette {extend: Extend::Mirror, ..palette("gray", &[(0.0, [0, 0, 0]), (1.0, [255, 255, 255])])},
+        ]
+    }
+
+    /** a builtin palette by its name or else a palette file **/
+    pub fn find(name: &str) -> Result<Palette, String> {
+        let builtins = Self::builtins();
+        let names = builtins.iter().map(|palette| palette.name.as_str()).collect::<Vec<_>>().join(", ");
+        match builtins.iter().find(|palette| palette.name == name) {
+            Some(palette) => Ok(palette.clone()),
+            None if Path::new(name).extension().is_none() => Err(format!("'{}': unknown palette, use {} or a palette file", name, names)),
+            None => Self::load(name.as_ref()),
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::builtins().remove(0)
+    }
+}
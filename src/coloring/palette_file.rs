@@ -0,0 +1,374 @@
+use std::{f32::consts::PI, fs, path::Path};
+use super::{color_space::ColorSpace, palette::{Extend, Interpolation, Palette, Stop}, Color};
+
+
+/** Stops per segment of a GIMP gradient, which approximate its blending functions **/
+const GGR_SAMPLES: usize = 8;
+
+impl Palette {
+    /** loads a palette file, the format is chosen by the extension:
+     *  - .ggr: GIMP gradient (HSV segments are blended in RGB, alpha is ignored)
+     *  - .map: Fractint colormap with one "r g b" line per iteration
+     *  - .csv: "position, r, g, b" or "position, #rrggbb" lines with positions in [0, 1] and channels in 0..255
+     *  - .json: {"stops": [{"position": 0, "color": "#000000"}, …]} with the optional settings "interpolation",
+     *    "space", "extend", "offset" and "density"
+     **/
+    pub fn load(path: &Path) -> Result<Palette, String> {
+        let name = path.display().to_string();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", name, e))?;
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        let palette = match extension.to_lowercase().as_str() {
+            "ggr" => parse_ggr(&text).and_then(|stops| Palette::new(&name, stops)),
+            "map" => parse_map(&text),
+            "csv" => parse_csv(&text).and_then(|stops| Palette::new(&name, stops)),
+            "json" => parse_json_palette(&name, &text),
+            _ => return Err(format!("{}: unknown palette format, use .ggr, .map, .csv or .json", name)),
+        };
+        palette.map(|palette| Palette {name: name.clone(), ..palette}).map_err(|message| format!("{}: {}", name, message))
+    }
+}
+
+
+fn parse_numbers(line: &str) -> Result<Vec<f32>, String> {
+    line.split_whitespace().map(|number| number.parse().map_err(|e| format!("'{}': {}", number, e))).collect()
+}
+
+/** > GIMP Gradient
+ *  > Name: …
+ *  > number of segments
+ *  > left middle right  r g b a (left)  r g b a (right)  blending coloring
+ **/
+fn parse_ggr(text: &str) -> Result<Vec<Stop>, String> {
+    let mut lines = text.lines().map(str::trim).enumerate().filter(|(_, line)| !line.is_empty());
+    if lines.next().map(|(_, line)| line) != Some("GIMP Gradient") {
+        return Err("expected the header 'GIMP Gradient'".to_string());
+    }
+    let mut lines = lines.filter(|(_, line)| !line.starts_with("Name:"));
+    let count = lines.next().and_then(|(_, line)| line.parse::<usize>().ok()).ok_or("expected the number of segments")?;
+
+    let mut stops = Vec::new();
+    for _ in 0..count {
+        let (n, line) = lines.next().ok_or("fewer segments than announced")?;
+        let at_line = |message: String| format!("line {}: {}", n + 1, message);
+        let v = parse_numbers(line).map_err(at_line)?;
+        if v.len() < 13 {
+            return Err(at_line("expected left, middle and right position, two RGBA colors and the blending".to_string()));
+        }
+        let (left, middle, right) = (v[0], v[1], v[2]);
+        let (a, b): (Color, Color) = ([v[3], v[4], v[5]], [v[7], v[8], v[9]]);
+        let blending = v[11] as i32;
+        let length = (right - left).max(f32::EPSILON);
+        let middle = ((middle - left) / length).clamp(f32::EPSILON, 1.0 - f32::EPSILON);
+        for k in 0..=GGR_SAMPLES {
+            let p = k as f32 / GGR_SAMPLES as f32;
+            /* like gimp_gradient_get_color_at */
+            let linear = if p <= middle { 0.5 * p / middle } else { 0.5 + 0.5 * (p - middle) / (1.0 - middle) };
+            let f = match blending {
+                1 => p.powf(0.5_f32.ln() / middle.ln()),
+                2 => ((-PI / 2.0 + PI * linear).sin() + 1.0) / 2.0,
+                3 => (1.0 - (linear - 1.0) * (linear - 1.0)).sqrt(),
+                4 => 1.0 - (1.0 - linear * linear).sqrt(),
+                _ => linear,
+            };
+            stops.push(Stop {position: (left + length * p).clamp(0.0, 1.0), color: [0, 1, 2].map(|c| a[c] + (b[c] - a[c]) * f)});
+        }
+    }
+    Ok(stops)
+}
+
+/** one color per iteration, the first 256 are used **/
+fn parse_map(text: &str) -> Result<Palette, String> {
+    let mut colors = Vec::new();
+    for (n, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).take(256) {
+        /* anything after the three channels is a comment */
+        let channels = line.split_whitespace().take(3).collect::<Vec<_>>().join(" ");
+        let v = parse_numbers(&channels).map_err(|message| format!("line {}: {}", n + 1, message))?;
+        if v.len() < 3 {
+            return Err(format!("line {}: expected r g b", n + 1));
+        }
+        colors.push([v[0] / 255.0, v[1] / 255.0, v[2] / 255.0]);
+    }
+    let n = colors.len() as f32;
+    let stops = colors.into_iter().enumerate().map(|(k, color)| Stop {position: k as f32 / n, color}).collect();
+    Ok(Palette {density: 1.0 / n, ..Palette::new("", stops)?})
+}
+
+fn parse_hex(hex: &str) -> Result<Color, String> {
+    let digits = hex.strip_prefix('#').filter(|digits| digits.len() == 6 && digits.is_ascii())
+        .ok_or(format!("'{}': expected a color like #ff8000", hex))?;
+    let channel = |k: usize| u8::from_str_radix(&digits[k..k+2], 16).map(|c| c as f32 / 255.0).map_err(|e| format!("'{}': {}", hex, e));
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/** lines starting with # are comments, a header line is skipped **/
+fn parse_csv(text: &str) -> Result<Vec<Stop>, String> {
+    let mut stops = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+        if line.trim().is_empty() || line.trim_start().starts_with('#') || (n == 0 && fields[0].parse::<f32>().is_err()) {
+            continue;
+        }
+        let at_line = |message: String| format!("line {}: {}", n + 1, message);
+        let position = fields[0].parse::<f32>().map_err(|e| at_line(format!("'{}': {}", fields[0], e)))?;
+        let color = match fields[1..] {
+            [hex] => parse_hex(hex).map_err(at_line)?,
+            [r, g, b] => {
+                let v = parse_numbers(&[r, g, b].join(" ")).map_err(at_line)?;
+                [v[0] / 255.0, v[1] / 255.0, v[2] / 255.0]
+            }
+            _ => return Err(at_line("expected position, r, g, b or position, #rrggbb".to_string())),
+        };
+        stops.push(Stop {position, color});
+    }
+    Ok(stops)
+}
+
+fn parse_json_palette(name: &str, text: &str) -> Result<Palette, String> {
+    let json = Json::parse(text)?;
+    let number = |value: &Json| match value { Json::Number(x) => Ok(*x as f32), _ => Err("expected a number".to_string()) };
+    let text = |value: &Json| match value { Json::Text(text) => Ok(text.clone()), _ => Err("expected a string".to_string()) };
+
+    let stops = match json.get("stops") {
+        Some(Json::Array(stops)) => stops,
+        _ => return Err("expected \"stops\": [{\"position\": 0, \"color\": \"#000000\"}, …]".to_string()),
+    };
+    let stops = stops.iter().map(|stop| {
+        let position = stop.get("position").ok_or("a stop without \"position\"".to_string()).and_then(number)?;
+        let color = match stop.get("color") {
+            Some(Json::Text(hex)) => parse_hex(hex)?,
+            Some(Json::Array(channels)) if channels.len() == 3 => {
+                let v = channels.iter().map(number).collect::<Result<Vec<_>, _>>()?;
+                [v[0] / 255.0, v[1] / 255.0, v[2] / 255.0]
+            }
+            _ => return Err("a stop without \"color\": \"#rrggbb\" or [r, g, b]".to_string()),
+        };
+        Ok(Stop {position, color})
+    }).collect::<Result<Vec<_>, String>>()?;
+
+    let mut palette = Palette::new(name, stops)?;
+    if let Some(value) = json.get("interpolation") {
+        palette.interpolation = Interpolation::parse(&text(value)?)?;
+    }
+    if let Some(value) = json.get("space") {
+        palette.space = ColorSpace::parse(&text(value)?)?;
+    }
+    if let Some(value) = json.get("extend") {
+        palette.extend = Extend::parse(&text(value)?)?;
+    }
+    if let Some(value) = json.get("offset") {
+        palette.offset = number(value)?;
+    }
+    if let Some(value) = json.get("density") {
+        palette.density = number(value)?;
+    }
+    Ok(palette)
+}
+
+
+/** Just enough JSON for palette files **/
+enum Json {
+    Literal,  // true, false or null, which palettes don't use
+    Number(f64),
+    Text(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(text: &str) -> Result<Json, String> {
+        let mut chars = text.chars().peekable();
+        let json = Self::value(&mut chars)?;
+        Self::skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(json),
+            Some(ch) => Err(format!("unexpected '{}' after the JSON value", ch)),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
+        while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+    }
+
+    fn value(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Json, String> {
+        Self::skip_whitespace(chars);
+        match chars.peek() {
+            Some('{') | Some('[') => {
+                let object = chars.next() == Some('{');
+                let close = if object { '}' } else { ']' };
+                let mut members = Vec::new();
+                Self::skip_whitespace(chars);
+                if chars.next_if_eq(&close).is_none() {
+                    loop {
+                        let key = if object {
+                            let Json::Text(key) = Self::value(chars)? else { return Err("expected a \"key\"".to_string()) };
+                            Self::skip_whitespace(chars);
+                            chars.next_if_eq(&':').ok_or("expected ':'")?;
+                            key
+                        } else {
+                            String::new()
+                        };
+                        members.push((key, Self::value(chars)?));
+                        Self::skip_whitespace(chars);
+                        match chars.next() {
+                            Some(',') => {}
+                            Some(ch) if ch == close => break,
+                            _ => return Err(format!("expected ',' or '{}'", close)),
+                        }
+                    }
+                }
+                Ok(if object { Json::Object(members) } else { Json::Array(members.into_iter().map(|(_, value)| value).collect()) })
+            }
+            Some('"') => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => return Ok(Json::Text(text)),
+                        Some('\\') => match chars.next() {
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            Some('u') => {
+                                let code = (0..4).filter_map(|_| chars.next()).collect::<String>();
+                                let ch = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32);
+                                text.push(ch.ok_or(format!("invalid escape \\u{}", code))?);
+                            }
+                            Some(ch) => text.push(ch),
+                            None => return Err("unterminated string".to_string()),
+                        },
+                        Some(ch) => text.push(ch),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+            }
+            Some(&first) => {
+                let mut word = String::new();
+                while let Some(ch) = chars.next_if(|ch| ch.is_alphanumeric() || "+-.".contains(*ch)) {
+                    word.push(ch);
+                }
+                match word.as_str() {
+                    "true" | "false" | "null" => Ok(Json::Literal),
+                    "" => Err(format!("unexpected '{}'", first)),
+                    _ => word.parse().map(Json::Number).map_err(|_| format!("unexpected '{}'", word)),
+                }
+            }
+            None => Err("unexpected end of JSON".to_string()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colors(stops: &[Stop]) -> Vec<(f32, Color)> {
+        stops.iter().map(|stop| (stop.position, stop.color)).collect()
+    }
+
+    #[test]
+    fn ggr() {
+        let stops = parse_ggr("GIMP Gradient\nName: test\n1\n0.0 0.5 1.0  0 0 0 1  1 0.5 0 1  0 0\n").expect("valid gradient");
+        assert_eq!(stops.len(), GGR_SAMPLES + 1);
+        assert_eq!(colors(&stops[..1]), [(0.0, [0.0, 0.0, 0.0])]);
+        assert_eq!(colors(&stops[GGR_SAMPLES / 2..GGR_SAMPLES / 2 + 1]), [(0.5, [0.5, 0.25, 0.0])]);
+        assert_eq!(colors(&stops[GGR_SAMPLES..]), [(1.0, [1.0, 0.5, 0.0])]);
+
+        /* curved blending bends the colors towards the middle of the segment */
+        let curved = parse_ggr("GIMP Gradient\n1\n0 0.25 1 0 0 0 1 1 1 1 1 1 0\n").expect("valid gradient");
+        assert!((curved[GGR_SAMPLES / 4].color[0] - 0.5).abs() < 1e-6);
+
+        for (text, message) in [
+            ("GIMP Palette\n1\n", "expected the header 'GIMP Gradient'"),
+            ("GIMP Gradient\nName: test\nmany\n", "expected the number of segments"),
+            ("GIMP Gradient\n2\n0 0.5 1 0 0 0 1 1 1 1 1 0 0\n", "fewer segments than announced"),
+            ("GIMP Gradient\n1\n0 0.5 1 0 0 0 1\n", "line 3: expected left, middle and right position, two RGBA colors and the blending"),
+            ("GIMP Gradient\n1\n0 0.5 x 0 0 0 1 1 1 1 1 0 0\n", "line 3: 'x': invalid float literal"),
+        ] {
+            assert_eq!(parse_ggr(text).err().as_deref(), Some(message), "{}", text);
+        }
+    }
+
+    #[test]
+    fn map() {
+        let palette = parse_map("0 0 0\n\n255 51 0 a comment\n").expect("valid colormap");
+        assert_eq!(colors(&palette.stops), [(0.0, [0.0, 0.0, 0.0]), (0.5, [1.0, 0.2, 0.0])]);
+        assert_eq!(palette.density, 0.5);
+        assert_eq!(parse_map(&"1 2 3\n".repeat(300)).expect("valid colormap").stops.len(), 256);
+
+        assert_eq!(parse_map("0 0 0\n255 255\n").err().as_deref(), Some("line 2: expected r g b"));
+        assert_eq!(parse_map("0 0 red\n").err().as_deref(), Some("line 1: 'red': invalid float literal"));
+        assert_eq!(parse_map("").err().as_deref(), Some("a palette needs at least one color"));
+    }
+
+    #[test]
+    fn csv() {
+        let stops = parse_csv("position, color\n# black to orange\n0, 0, 0, 0\n\n1.0, #ff3300\n").expect("valid csv");
+        assert_eq!(colors(&stops), [(0.0, [0.0, 0.0, 0.0]), (1.0, [1.0, 0.2, 0.0])]);
+
+        for (text, message) in [
+            ("0, 0, 0\n", "line 1: expected position, r, g, b or position, #rrggbb"),
+            ("0, 0, 0, 0\nx, 1, 1, 1\n", "line 2: 'x': invalid float literal"),
+            ("0, #ff33\n", "line 1: '#ff33': expected a color like #ff8000"),
+            ("0, #gg0000\n", "line 1: '#gg0000': invalid digit found in string"),
+            ("0, 0, 0, blue\n", "line 1: 'blue': invalid float literal"),
+        ] {
+            assert_eq!(parse_csv(text).err().as_deref(), Some(message), "{}", text);
+        }
+    }
+
+    #[test]
+    fn json() {
+        let text = r##"{
+            "stops": [{"position": 0, "color": "#000000"}, {"position": 1e0, "color": [255, 51, 0], "comment": "orange!\n"}],
+            "interpolation": "smooth", "space": "linear", "extend": "mirror", "offset": -2.5, "density": 0.125, "dark": true
+        }"##;
+        let palette = parse_json_palette("test", text).expect("valid palette");
+        assert_eq!(colors(&palette.stops), [(0.0, [0.0, 0.0, 0.0]), (1.0, [1.0, 0.2, 0.0])]);
+        assert_eq!((palette.interpolation, palette.space, palette.extend), (Interpolation::Smooth, ColorSpace::Linear, Extend::Mirror));
+        assert_eq!((palette.offset, palette.density), (-2.5, 0.125));
+
+        for (text, message) in [
+            ("[]", "expected \"stops\": [{\"position\": 0, \"color\": \"#000000\"}, …]"),
+            (r##"{"stops": [{"color": "#000000"}]}"##, "a stop without \"position\""),
+            (r##"{"stops": [{"position": "0", "color": "#000000"}]}"##, "expected a number"),
+            (r##"{"stops": [{"position": 0, "color": [0, 0]}]}"##, "a stop without \"color\": \"#rrggbb\" or [r, g, b]"),
+            (r##"{"stops": [{"position": 2, "color": "#000000"}]}"##, "the position 2 is not in [0, 1]"),
+            (r##"{"stops": [{"position": 0, "color": "#000000"}], "extend": "wrap"}"##, "'wrap': unknown extend, use clamp, repeat or mirror"),
+            (r##"{"stops": [{"position": 0, "color": "#000000"}], "density": "dense"}"##, "expected a number"),
+            (r##"{"stops": [] } x"##, "unexpected 'x' after the JSON value"),
+            (r##"{"stops": [1 2]}"##, "expected ',' or ']'"),
+            (r##"{"stops" [1]}"##, "expected ':'"),
+            (r##"{1: 2}"##, "expected a \"key\""),
+            (r##"{"stops": "unterminated}"##, "unterminated string"),
+            (r##"{"stops": "\uzzzz"}"##, "invalid escape \\uzzzz"),
+            (r##"{"stops": @}"##, "unexpected '@'"),
+            (r##"{"stops": "##, "unexpected end of JSON"),
+        ] {
+            assert_eq!(parse_json_palette("test", text).err().as_deref(), Some(message), "{}", text);
+        }
+    }
+
+    #[test]
+    fn load() {
+        let directory = std::env::temp_dir().join(format!("fractals-palette-{}", std::process::id()));
+        fs::create_dir_all(&directory).expect("temporary directory");
+        let (valid, invalid, unknown) = (directory.join("valid.CSV"), directory.join("invalid.map"), directory.join("palette.txt"));
+        fs::write(&valid, "0, #000000\n1, #ffffff\n").expect("written");
+        fs::write(&invalid, "0 0\n").expect("written");
+        fs::write(&unknown, "").expect("written");
+
+        let palette = Palette::load(&valid).expect("valid palette");
+        assert_eq!((palette.name, palette.stops.len()), (valid.display().to_string(), 2));
+        assert_eq!(Palette::load(&invalid).err(), Some(format!("{}: line 1: expected r g b", invalid.display())));
+        assert_eq!(Palette::load(&unknown).err(), Some(format!("{}: unknown palette format, use .ggr, .map, .csv or .json", unknown.display())));
+        assert!(Palette::load(&directory.join("missing.csv")).is_err());
+        fs::remove_dir_all(&directory).expect("removed");
+    }
+}
@@ -0,0 +1,217 @@
+use num::{BigInt, Float, Signed, ToPrimitive, Zero};
+use std::{fmt, ops::{Add, Mul, Sub}};
+
+
+/** Arbitrary precision fixed point number m * 2^-bits
+ *  - bits defines the precision and should grow with the zoom depth
+ *  - operands of different precision are rescaled to the precision of the left hand side
+ **/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigFixed {
+    m: BigInt,
+    bits: u32,
+}
+
+impl BigFixed {
+    pub fn zero(bits: u32) -> Self {
+        Self {m: BigInt::zero(), bits}
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /** the mantissa scaled to another precision (rounding towards negative infinity) **/
+    fn m_with_bits(&self, bits: u32) -> BigInt {
+        if bits >= self.bits {
+            &self.m << (bits - self.bits)
+        } else {
+            &self.m >> (self.bits - bits)
+        }
+    }
+
+    pub fn with_bits(&self, bits: u32) -> Self {
+        Self {m: self.m_with_bits(bits), bits}
+    }
+
+    /** exact conversion, as long as bits is sufficient for the fractional digits of x **/
+    pub fn from_f64(x: f64, bits: u32) -> Self {
+        if !x.is_finite() {
+            return Self::zero(bits);
+        }
+        let (mantissa, exponent, sign) = x.integer_decode();
+        let m = BigInt::from(mantissa) * sign;
+        let shift = exponent as i64 + bits as i64;
+        let m = if shift >= 0 { m << shift } else { m >> -shift };
+        Self {m, bits}
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        /* keep the 64 most significant bits, the rest can't be represented anyway */
+        let excess = (self.m.bits() as i64 - 64).max(0);
+        let m = (&self.m >> excess).to_f64().unwrap_or(f64::NAN);
+        /* 2^e may be out of range for f64, while the result is not */
+        let e = excess - self.bits as i64;
+        m * 2.0_f64.powi((e / 2) as i32) * 2.0_f64.powi((e - e / 2) as i32)
+    }
+
+    /** parses decimal numbers like "-0.743643887037158704752191506114774" or "1.5e-30" **/
+    pub fn parse(s: &str, bits: u32) -> Result<Self, String> {
+        let invalid = || format!("invalid decimal number '{}'", s);
+        let (mantissa, exponent) = match s.trim().split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().map_err(|_| invalid())?),
+            None => (s.trim(), 0),
+        };
+        let (negative, digits) = match mantissa.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        if int.is_empty() && frac.is_empty() || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        /* value = n * 10^exponent */
+        let n = BigInt::parse_bytes(format!("0{}{}", int, frac).as_bytes(), 10).ok_or_else(invalid)?;
+        let exponent = exponent - frac.len() as i64;
+        let ten = BigInt::from(10);
+        let m = if exponent >= 0 {
+            (n * ten.pow(exponent as u32)) << bits
+        } else {
+            let scale = ten.pow((-exponent) as u32);
+            ((n << bits) + (&scale >> 1)) / scale
+        };
+        Ok(Self {m: if negative { -m } else { m }, bits})
+    }
+
+    /** decimal representation rounded to a number of fractional digits, without trailing zeros **/
+    pub fn to_decimal(&self, digits: u32) -> String {
+        let magnitude = self.m.abs();
+        let int = &magnitude >> self.bits;
+        let frac = magnitude - (&int << self.bits);
+
+        let scale = BigInt::from(10).pow(digits);
+        let half: BigInt = BigInt::from(1) << self.bits >> 1;
+        let frac: BigInt = (frac * &scale + half) >> self.bits;
+        let (int, frac) = if frac >= scale { (int + 1, frac - scale) } else { (int, frac) };
+
+        let frac = format!("{:0>width$}", frac.to_string(), width = digits as usize);
+        let frac = frac.trim_end_matches('0');
+        let sign = if self.m.is_negative() { "-" } else { "" };
+        format!("{}{}.{}", sign, int, if frac.is_empty() { "0" } else { frac })
+    }
+}
+
+/** decimal representation with enough digits to parse it back without loss **/
+impl fmt::Display for BigFixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = (self.bits as f64 * 2.0_f64.log10()).ceil() as u32 + 1;
+        write!(f, "{}", self.to_decimal(digits))
+    }
+}
+
+impl Add for &BigFixed {
+    type Output = BigFixed;
+    fn add(self, rhs: &BigFixed) -> BigFixed {
+        BigFixed {m: &self.m + rhs.m_with_bits(self.bits), bits: self.bits}
+    }
+}
+
+impl Sub for &BigFixed {
+    type Output = BigFixed;
+    fn sub(self, rhs: &BigFixed) -> BigFixed {
+        BigFixed {m: &self.m - rhs.m_with_bits(self.bits), bits: self.bits}
+    }
+}
+
+impl Mul for &BigFixed {
+    type Output = BigFixed;
+    fn mul(self, rhs: &BigFixed) -> BigFixed {
+        BigFixed {m: (&self.m * &rhs.m) >> rhs.bits, bits: self.bits}
+    }
+}
+
+
+/** Arbitrary precision complex number **/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigComplex {
+    pub re: BigFixed,
+    pub im: BigFixed,
+}
+
+impl BigComplex {
+    pub fn zero(bits: u32) -> Self {
+        Self {re: BigFixed::zero(bits), im: BigFixed::zero(bits)}
+    }
+
+    pub fn from_f64(re: f64, im: f64, bits: u32) -> Self {
+        Self {re: BigFixed::from_f64(re, bits), im: BigFixed::from_f64(im, bits)}
+    }
+
+    pub fn with_bits(&self, bits: u32) -> Self {
+        Self {re: self.re.with_bits(bits), im: self.im.with_bits(bits)}
+    }
+
+    pub fn to_f64(&self) -> (f64, f64) {
+        (self.re.to_f64(), self.im.to_f64())
+    }
+
+    pub fn add(&self, rhs: &BigComplex) -> Self {
+        Self {re: &self.re + &rhs.re, im: &self.im + &rhs.im}
+    }
+
+    /** z² + c **/
+    pub fn square_add(&self, c: &BigComplex) -> Self {
+        let re = &(&(&self.re * &self.re) - &(&self.im * &self.im)) + &c.re;
+        let re_im = &self.re * &self.im;
+        let im = &(&re_im + &re_im) + &c.im;
+        Self {re, im}
+    }
+}
+
+impl fmt::Display for BigComplex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.im.m.is_negative() {
+            write!(f, "{}{}i", self.re, self.im)
+        } else {
+            write!(f, "{}+{}i", self.re, self.im)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_to_decimal() {
+        for (s, digits, decimal) in [
+            ("0", 5, "0.0"),
+            ("-1.5", 3, "-1.5"),
+            ("+2.25", 3, "2.25"),
+            ("1.5e-3", 10, "0.0015"),
+            ("-12.5e2", 2, "-1250.0"),
+            ("0.999", 2, "1.0"),
+            ("-0.743643887037158704752191506114774", 33, "-0.743643887037158704752191506114774"),
+        ] {
+            assert_eq!(BigFixed::parse(s, 128).expect("valid number").to_decimal(digits), decimal, "{}", s);
+        }
+        for s in ["", ".", "-", "1.2.3", "1e", "0x10", "1,5"] {
+            assert!(BigFixed::parse(s, 64).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn display_parse() {
+        for s in ["0.131825904205311970493132056385139", "-1.25", "3.0e-40"] {
+            for bits in [64, 200, 500] {
+                let x = BigFixed::parse(s, bits).expect("valid number");
+                assert_eq!(BigFixed::parse(&x.to_string(), bits).expect("valid number"), x);
+            }
+        }
+        let x = BigFixed::from_f64(-0.1, 80);
+        assert_eq!(x.to_f64(), -0.1);
+        assert_eq!(BigFixed::parse(&x.to_string(), 80).expect("valid number"), x);
+    }
+}
@@ -0,0 +1,171 @@
+use num::Complex;
+use rand::prelude::*;
+use rayon::prelude::*;
+use crate::coloring::color_space::encode;
+use crate::image::Image;
+use super::{periodicity::cardioid_or_bulb, plane::Plane};
+
+
+/** Sampled values of c are restricted to |Re c|, |Im c| <= SAMPLED_RADIUS, outside the orbits escape at once **/
+const SAMPLED_RADIUS: f64 = 2.0;
+
+/** Cells per axis of the grid, which estimates where sampling c is worthwhile **/
+const IMPORTANCE_GRID: usize = 256;
+
+/** Importance of cells, which seem to contain no escaping orbits, so that no region is excluded entirely **/
+const IMPORTANCE_FLOOR: f64 = 0.05;
+
+/** Tone mapping: the density at this percentile of the visited pixels of a channel is mapped to full brightness **/
+const PERCENTILE: f64 = 0.995;
+
+/** Density of the orbits of z² + c, which escape, over a target plane (the Buddhabrot)
+ *  - each channel (red, green, blue) only records orbits escaping within its limit of iterations, so distinct
+ *    limits combine into the Nebulabrot and equal limits into the classical, gray Buddhabrot
+ *  - c is sampled preferably near the boundary of the Mandelbrot set, where the long orbits start, and each orbit is
+ *    weighted inversely, so the density doesn't depend on the importance sampling
+ *  - sampling refines the densities progressively
+ **/
+pub struct Buddhabrot {
+    pub plane: Plane<f64>,
+    pub limits: [i32; 3],
+    pub density: [Vec<f64>; 3],  // weighted hits per pixel, row by row
+    pub samples: u64,            // c sampled so far
+    cumulative: Vec<f64>,        // importance of the cells of the grid, summed up to each cell
+}
+
+impl Buddhabrot {
+    pub fn new(plane: Plane<f64>, limits: [i32; 3]) -> Self {
+        let limit = limits.iter().copied().max().unwrap_or(0).max(1);
+        let cell = 2.0 * SAMPLED_RADIUS / IMPORTANCE_GRID as f64;
+        let center = |k: usize| -SAMPLED_RADIUS + (k as f64 + 0.5) * cell;
+        /* the iteration of escape at the center of each cell, None if it doesn't escape */
+        let escape = (0..IMPORTANCE_GRID * IMPORTANCE_GRID).into_par_iter()
+            .map(|k| escape(Complex::new(center(k % IMPORTANCE_GRID), center(k / IMPORTANCE_GRID)), limit))
+            .collect::<Vec<_>>();
+
+        let importance = (0..escape.len()).map(|k| {
+            let (x, y) = (k % IMPORTANCE_GRID, k / IMPORTANCE_GRID);
+            let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+            let boundary = neighbours.iter()
+                .filter(|&&(x, y)| x < IMPORTANCE_GRID && y < IMPORTANCE_GRID)
+                .any(|&(x, y)| escape[y * IMPORTANCE_GRID + x].is_some() != escape[k].is_some());
+            /* long orbits are rare and reach furthest into the interesting regions */
+            let late = escape[k].map_or(0.0, |n| n as f64 / limit as f64);
+            IMPORTANCE_FLOOR + late + if boundary { 1.0 } else { 0.0 }
+        });
+        let cumulative = importance.scan(0.0, |sum, importance| {
+            *sum += importance;
+            Some(*sum)
+        }).collect();
+
+        let pixels = plane.width * plane.height;
+        Self {plane, limits, density: [0, 1, 2].map(|_| vec![0.0; pixels]), samples: 0, cumulative}
+    }
+
+    /** records the escaping orbits of n more random values of c
+     *  - each thread accumulates its own densities, which are summed up at the end, so no orbit is kept
+     **/
+    pub fn sample(&mut self, n: u64) {
+        let limit = self.limits.iter().copied().max().unwrap_or(0);
+        let total = self.cumulative.last().copied().unwrap_or(0.0);
+        let cells = self.cumulative.len() as f64;
+        let cell = 2.0 * SAMPLED_RADIUS / IMPORTANCE_GRID as f64;
+        let (plane, limits, cumulative) = (&self.plane, self.limits, &self.cumulative);
+        let pixels = plane.width * plane.height;
+
+        /* a random c, the iteration of its escape, the channels recording it and its weight */
+        let sample = |rng: &mut ThreadRng| {
+            /* a cell by its importance, then uniformly within it */
+            let u = rng.gen::<f64>() * total;
+            let k = cumulative.partition_point(|&sum| sum < u).min(cumulative.len() - 1);
+            let importance = cumulative[k] - if k > 0 { cumulative[k - 1] } else { 0.0 };
+            let (x, y) = (k % IMPORTANCE_GRID, k / IMPORTANCE_GRID);
+            let c = Complex::new(
+                -SAMPLED_RADIUS + (x as f64 + rng.gen::<f64>()) * cell,
+                -SAMPLED_RADIUS + (y as f64 + rng.gen::<f64>()) * cell,
+            );
+            let n = escape(c, limit)?;
+            let channels = [0, 1, 2].map(|k| n <= limits[k]);
+            /* the uniform density relative to the density, with which c was sampled */
+            let weight = total / (cells * importance);
+            Some((c, n, channels, weight))
+        };
+
+        let sampled = (0..n).into_par_iter()
+            .fold(|| [0, 1, 2].map(|_| vec![0.0; pixels]), |mut density, _| {
+                if let Some((c, n, channels, weight)) = sample(&mut thread_rng()) {
+                    orbit(c, n, plane, |pixel| {
+                        for (density, _) in density.iter_mut().zip(channels).filter(|(_, recorded)| *recorded) {
+                            density[pixel] += weight;
+                        }
+                    });
+                }
+                density
+            })
+            .reduce_with(|mut sum, density| {
+                add(&mut sum, &density);
+                sum
+            });
+        if let Some(sampled) = sampled {
+            add(&mut self.density, &sampled);
+        }
+        self.samples += n;
+    }
+
+    /** the densities tone mapped into colors
+     *  - each channel is scaled, so the density at PERCENTILE of its visited pixels becomes 1
+     *  - gamma < 1 brightens the faint orbits
+     **/
+    pub fn image(&self, gamma: f32) -> Image {
+        let scale = self.density.each_ref().map(|density| {
+            let mut visited = density.iter().copied().filter(|&d| d > 0.0).collect::<Vec<_>>();
+            visited.sort_by(f64::total_cmp);
+            let reference = visited.get(((visited.len() as f64 * PERCENTILE) as usize).min(visited.len().saturating_sub(1)));
+            reference.map_or(0.0, |&reference| 1.0 / reference)
+        });
+        Image::from_fn(self.plane.width, self.plane.height, |x, y| {
+            let k = y * self.plane.width + x;
+            encode([0, 1, 2].map(|c| ((self.density[c][k] * scale[c]) as f32).powf(gamma)))
+        })
+    }
+}
+
+
+/** the iteration, in which the orbit of z² + c escapes, None if it doesn't within limit iterations **/
+fn escape(c: Complex<f64>, limit: i32) -> Option<i32> {
+    if cardioid_or_bulb(c) != 0 {
+        return None;
+    }
+    let mut z = Complex::new(0.0, 0.0);
+    for n in 1..=limit {
+        z = z * z + c;
+        if z.norm_sqr() > 4.0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+/** the pixels of the plane, which the first n states of the orbit of z² + c hit, passed to hit **/
+fn orbit(c: Complex<f64>, n: i32, plane: &Plane<f64>, mut hit: impl FnMut(usize)) {
+    let (width, height) = (plane.width as f64, plane.height as f64);
+    let mut z = Complex::new(0.0, 0.0);
+    for _ in 0..n {
+        z = z * z + c;
+        /* the nearest coordinate, which samples at the corner of its pixel */
+        let (x, y) = plane.c_to_xy(z);
+        let (x, y) = ((x + 0.5).floor(), (y + 0.5).floor());
+        if (0.0..width).contains(&x) && (0.0..height).contains(&y) {
+            hit(y as usize * plane.width + x as usize);
+        }
+    }
+}
+
+/** adds the densities of each channel **/
+fn add(sum: &mut [Vec<f64>; 3], density: &[Vec<f64>; 3]) {
+    for (sum, density) in sum.iter_mut().zip(density) {
+        for (sum, density) in sum.iter_mut().zip(density) {
+            *sum += density;
+        }
+    }
+}
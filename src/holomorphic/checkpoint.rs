@@ -0,0 +1,249 @@
+use num::Complex;
+use std::{fs, io::{BufReader, BufWriter, Read, Write}, path::{Path, PathBuf}};
+use super::{dynamic::HolomorphicDynamic, session::Session, simulation::D_MAX};
+
+
+const MAGIC: &[u8; 8] = b"FRACTALS";
+const VERSION: u32 = 1;
+
+/** Binary snapshot of a simulation, which can be resumed by Simulation::steps
+ *
+ *  All numbers are little endian:
+ *  > "FRACTALS", version: u32, length: u32, session: [u8; length] (the text of Session::with_dynamic)
+ *  > i: i32, derivative: u8 (whether dz is tracked), d: [i32], period: [i32], z: [(f64, f64)]
+ *  > dz: [(f64, f64)] only if the derivative is tracked
+ *  > z_saved: [(f64, f64)] only for pixels, which are still iterated
+ *  > perturbed: u8, if 1: delta: [(f64, f64)], n: [u64]
+ *
+ *  The function can't be serialized, so it is rebuilt from the formula of the session.
+ **/
+pub fn write(writer: &mut impl Write, session: &Session, dynamic: &HolomorphicDynamic<f64>) -> std::io::Result<()> {
+    let header = session.with_dynamic(dynamic).to_string();
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(header.len() as u32).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    writer.write_all(&dynamic.i.to_le_bytes())?;
+    writer.write_all(&[dynamic.derivative as u8])?;
+
+    for d in dynamic.d.iter().chain(&dynamic.period).flatten() {
+        writer.write_all(&d.to_le_bytes())?;
+    }
+    let dz = if dynamic.derivative { dynamic.dz.as_slice() } else { &[] };
+    for &z in dynamic.z.iter().chain(dz).flatten() {
+        write_complex(writer, z)?;
+    }
+    for (y, row) in dynamic.z_saved.iter().enumerate() {
+        for (x, &z) in row.iter().enumerate() {
+            if iterated(dynamic, x, y) {
+                write_complex(writer, z)?;
+            }
+        }
+    }
+
+    match &dynamic.perturbation {
+        None => writer.write_all(&[0]),
+        Some(perturbation) => {
+            writer.write_all(&[1])?;
+            for &delta in perturbation.delta.iter().flatten() {
+                write_complex(writer, delta)?;
+            }
+            for &n in perturbation.n.iter().flatten() {
+                writer.write_all(&(n as u64).to_le_bytes())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/** the session and the dynamic of a checkpoint **/
+pub fn read(reader: &mut impl Read) -> Result<(Session, HolomorphicDynamic<f64>), String> {
+    if &read_bytes::<8>(reader)? != MAGIC {
+        return Err("not a checkpoint".to_string());
+    }
+    let version = u32::from_le_bytes(read_bytes(reader)?);
+    if version != VERSION {
+        return Err(format!("unsupported checkpoint version {}, expected {}", version, VERSION));
+    }
+    let length = u32::from_le_bytes(read_bytes(reader)?);
+    let mut header = vec![0; length as usize];
+    reader.read_exact(&mut header).map_err(truncated)?;
+    let header = String::from_utf8(header).map_err(|e| e.to_string())?;
+    let session = Session::parse(&header)?;
+
+    let mut dynamic = session.dynamic()?;
+    /* exactly the saved plane and center, not recentered by Mandelbrot::perturbed */
+    dynamic.plane = session.plane;
+    if let (Some(perturbation), Some(center)) = (dynamic.perturbation.as_mut(), &session.center) {
+        perturbation.center = center.clone();
+    }
+    dynamic.i = i32::from_le_bytes(read_bytes(reader)?);
+    dynamic.derivative = read_bytes::<1>(reader)?[0] == 1;
+
+    for rows in [&mut dynamic.d, &mut dynamic.period] {
+        for d in rows.iter_mut().flatten() {
+            *d = i32::from_le_bytes(read_bytes(reader)?);
+        }
+    }
+    let dz = if dynamic.derivative { dynamic.dz.as_mut_slice() } else { &mut [] };
+    for rows in [dynamic.z.as_mut_slice(), dz] {
+        for z in rows.iter_mut().flatten() {
+            *z = read_complex(reader)?;
+        }
+    }
+    for y in 0..dynamic.plane.height {
+        for x in 0..dynamic.plane.width {
+            if iterated(&dynamic, x, y) {
+                dynamic.z_saved[y][x] = read_complex(reader)?;
+            }
+        }
+    }
+
+    let perturbed = read_bytes::<1>(reader)?[0] == 1;
+    match dynamic.perturbation.as_mut() {
+        Some(perturbation) if perturbed => {
+            for delta in perturbation.delta.iter_mut().flatten() {
+                *delta = read_complex(reader)?;
+            }
+            for n in perturbation.n.iter_mut().flatten() {
+                *n = u64::from_le_bytes(read_bytes(reader)?) as usize;
+            }
+        }
+        None if !perturbed => {}
+        _ => return Err("the perturbation does not match the center of the session".to_string()),
+    }
+    if reader.read(&mut [0]).map_err(|e| e.to_string())? != 0 {
+        return Err("unexpected data after the checkpoint".to_string());
+    }
+    Ok((session, dynamic))
+}
+
+/** writes a checkpoint without ever leaving a partially written file behind **/
+pub fn save(path: &Path, session: &Session, dynamic: &HolomorphicDynamic<f64>) -> Result<(), String> {
+    let partial = PathBuf::from(format!("{}.partial", path.display()));
+    let written = fs::File::create(&partial).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer, session, dynamic)?;
+        writer.into_inner()?.sync_all()
+    });
+    written.and_then(|_| fs::rename(&partial, path)).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn load(path: &Path) -> Result<(Session, HolomorphicDynamic<f64>), String> {
+    let file = fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    read(&mut BufReader::new(file)).map_err(|message| format!("{}: {}", path.display(), message))
+}
+
+
+/** whether z_saved is still needed to detect cycles **/
+fn iterated(dynamic: &HolomorphicDynamic<f64>, x: usize, y: usize) -> bool {
+    dynamic.d[y][x] == D_MAX && dynamic.period[y][x] == 0
+}
+
+fn write_complex(writer: &mut impl Write, z: Complex<f64>) -> std::io::Result<()> {
+    writer.write_all(&z.re.to_le_bytes())?;
+    writer.write_all(&z.im.to_le_bytes())
+}
+
+fn read_complex(reader: &mut impl Read) -> Result<Complex<f64>, String> {
+    Ok(Complex::new(f64::from_le_bytes(read_bytes(reader)?), f64::from_le_bytes(read_bytes(reader)?)))
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], String> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes).map_err(truncated)?;
+    Ok(bytes)
+}
+
+fn truncated(e: std::io::Error) -> String {
+    match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => "truncated checkpoint".to_string(),
+        _ => e.to_string(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::holomorphic::{bigfixed::{BigComplex, BigFixed}, plane::Plane, simulation::Simulation};
+
+    /** a session iterated halfway and its checkpoint **/
+    fn checkpoint(session: &Session, derivative: bool) -> (HolomorphicDynamic<f64>, Vec<u8>) {
+        let mut dynamic = session.dynamic().expect("valid session");
+        dynamic.derivative = derivative;
+        dynamic.steps(session.iterations / 2);
+        let mut bytes = Vec::new();
+        write(&mut bytes, session, &dynamic).expect("written to memory");
+        (dynamic, bytes)
+    }
+
+    fn assert_resumed(dynamic: &HolomorphicDynamic<f64>, resumed: &HolomorphicDynamic<f64>) {
+        assert_eq!(resumed.i, dynamic.i);
+        assert_eq!(resumed.derivative, dynamic.derivative);
+        assert!(resumed.z == dynamic.z);
+        assert!(!dynamic.derivative || resumed.dz == dynamic.dz);
+        assert_eq!(resumed.d, dynamic.d);
+        assert_eq!(resumed.period, dynamic.period);
+    }
+
+    fn plane(width: usize, height: usize, size: f64) -> Plane<f64> {
+        Plane {re_min: -size, re_max: size, im_min: -size, im_max: size, rotation: 0.0, width, height}
+    }
+
+    #[test]
+    fn round_trip() {
+        let session = Session {plane: Plane {re_min: -2.0, re_max: 0.5, ..plane(24, 16, 1.2)}, iterations: 40, ..Session::default()};
+        let (dynamic, bytes) = checkpoint(&session, true);
+        let (resumed_session, resumed) = read(&mut bytes.as_slice()).expect("valid checkpoint");
+        assert_eq!(resumed_session.to_string(), session.with_dynamic(&dynamic).to_string());
+        assert_resumed(&dynamic, &resumed);
+        assert!(resumed.perturbation.is_none());
+
+        /* without the derivative, dz is left out */
+        let (dynamic, compact) = checkpoint(&session, false);
+        let (_, resumed) = read(&mut compact.as_slice()).expect("valid checkpoint");
+        assert_resumed(&dynamic, &resumed);
+        assert_eq!(bytes.len() - compact.len(), 24 * 16 * 16);
+    }
+
+    #[test]
+    fn round_trip_perturbed() {
+        let center = BigComplex {
+            re: BigFixed::parse("-0.743643887037158704752191506114774", 128).expect("valid number"),
+            im: BigFixed::parse("0.131825904205311970493132056385139", 128).expect("valid number"),
+        };
+        let session = Session {center: Some(center), plane: plane(16, 16, 1e-15), iterations: 400, ..Session::default()};
+        let (dynamic, bytes) = checkpoint(&session, true);
+        let (resumed_session, resumed) = read(&mut bytes.as_slice()).expect("valid checkpoint");
+        assert_eq!(resumed_session.to_string(), session.with_dynamic(&dynamic).to_string());
+        assert_resumed(&dynamic, &resumed);
+        let (perturbation, resumed) = (dynamic.perturbation.as_ref().expect("perturbed"), resumed.perturbation.as_ref().expect("perturbed"));
+        assert_eq!(resumed.center.to_string(), perturbation.center.to_string());
+        assert!(resumed.delta == perturbation.delta);
+        assert_eq!(resumed.n, perturbation.n);
+    }
+
+    #[test]
+    fn invalid() {
+        let (_, bytes) = checkpoint(&Session {plane: plane(8, 8, 2.0), iterations: 10, ..Session::default()}, true);
+        let error = |bytes: &[u8]| read(&mut &bytes[..]).err().expect("invalid checkpoint");
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert_eq!(error(&magic), "not a checkpoint");
+
+        for other in [VERSION - 1, VERSION + 1] {
+            let mut version = bytes.clone();
+            version[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&other.to_le_bytes());
+            assert!(error(&version).starts_with("unsupported checkpoint version"));
+        }
+
+        assert_eq!(error(&bytes[..bytes.len() - 1]), "truncated checkpoint");
+        assert_eq!(error(&bytes[..MAGIC.len() + 2]), "truncated checkpoint");
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(error(&trailing), "unexpected data after the checkpoint");
+    }
+}
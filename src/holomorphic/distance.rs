@@ -0,0 +1,22 @@
+use super::{dynamic::{DynamicKind, HolomorphicDynamic}, precision::Precision, simulation::D_MAX};
+
+
+impl<T: Precision> HolomorphicDynamic<T> {
+    /** the estimated distance of a coordinate to the set, in pixels
+     *  - outside of the set, the potential G = log|z| / degree^i has the gradient |dz| / (|z| · degree^i),
+     *    so G / |∇G| = |z| · log|z| / |dz| estimates the distance (up to a factor of 4)
+     *  - for coordinates, which have not diverged (yet), None is returned
+     *  - the Newton method doesn't diverge, so there is no estimate either
+     **/
+    pub fn distance(&self, x: usize, y: usize) -> Option<f64> {
+        if self.d[y][x] == D_MAX || self.kind == DynamicKind::Newton {
+            return None;
+        }
+        let z = self.z[y][x].norm().to_f64()?;
+        let dz = self.dz[y][x].norm().to_f64()?;
+        let pixel_size = self.plane.pixel_size().abs().to_f64()?;
+        let distance = z * z.ln() / dz / pixel_size;
+        /* the derivative overflows arbitrarily close to the set */
+        Some(if distance.is_nan() { 0.0 } else { distance })
+    }
+}
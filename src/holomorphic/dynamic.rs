@@ -1,20 +1,118 @@
-use num::{complex::Complex32, Complex};
-use super::{r#fn::HolomorphicFn, plane::Plane, simulation::D_MAX};
+use num::Complex;
+use std::cmp::Ordering;
+use super::{bigfixed::BigComplex, perturbation::Perturbation, precision::Precision, r#fn::HolomorphicFn, plane::Plane, simulation::{D_MAX, SKIPPED}};
 
 
+/** How the initial state z0 is derived from the plane **/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DynamicKind {
+    Juliaset,    // z0 = c
+    Mandelbrot,  // z0 = 0
+    Newton,      // z0 = c, converging instead of diverging
+}
+
+/** The precision T is chosen by the plane, e.g. Plane<f64> for deeper zooms **/
 #[derive(Clone)]
-pub struct HolomorphicDynamic {
-    pub plane: Plane,
-    pub f: Box<dyn HolomorphicFn>,
-    pub z: Vec<Vec<Complex32>>,  // state
-    pub d: Vec<Vec<i32>>,        // diverged
-    pub i: i32,                  // iteration
+pub struct HolomorphicDynamic<T: Precision = f32> {
+    pub kind: DynamicKind,
+    pub plane: Plane<T>,
+    pub f: Box<dyn HolomorphicFn<T>>,
+    pub z: Vec<Vec<Complex<T>>>,  // state
+    pub dz: Vec<Vec<Complex<T>>>, // derivative of the state by c, which is z0 for Julia sets
+    pub derivative: bool,         // whether dz is tracked, which only the distance coloring needs
+    pub d: Vec<Vec<i32>>,         // diverged (converged for Newton)
+    pub period: Vec<Vec<i32>>,    // of the cycle of known interior points, 0 if unknown
+    pub(crate) z_saved: Vec<Vec<Complex<T>>>,  // for detecting cycles
+    pub i: i32,                   // iteration
+    pub perturbation: Option<Perturbation<T>>,  // deep zoom, the plane is relative to its center
+    pub roots: Vec<Complex<T>>,   // attractors of the Newton method
+    pub(crate) resumed: i32,      // iteration, at which the coordinates taken over from another dynamic continue
 }
 
-impl HolomorphicDynamic {
-    fn new(plane: Plane, f: impl HolomorphicFn + 'static, z0: Vec<Vec<Complex32>>) -> Self {
+impl<T: Precision> HolomorphicDynamic<T> {
+    pub(crate) fn new(kind: DynamicKind, plane: Plane<T>, f: Box<dyn HolomorphicFn<T>>) -> Self {
+        let z0 = match kind {
+            DynamicKind::Juliaset | DynamicKind::Newton => plane.c(),
+            DynamicKind::Mandelbrot => vec![vec![Complex::new(T::zero(), T::zero()); plane.width]; plane.height],
+        };
+        let dz0 = match kind {
+            DynamicKind::Juliaset | DynamicKind::Newton => Complex::new(T::one(), T::zero()),
+            DynamicKind::Mandelbrot => Complex::new(T::zero(), T::zero()),
+        };
+        let dz = vec![vec![dz0; plane.width]; plane.height];
         let d = vec![vec![D_MAX; plane.width]; plane.height];
-        Self {plane, f: Box::new(f), z: z0, d, i: 0}
+        let period = vec![vec![0; plane.width]; plane.height];
+        let z_saved = z0.clone();
+        Self {kind, plane, f, z: z0, dz, derivative: false, d, period, z_saved, i: 0, perturbation: None, roots: Vec::new(), resumed: 0}
+    }
+
+    /** a dynamic of the same kind and function, restarted on another plane **/
+    pub fn with_plane(&self, plane: Plane<T>) -> Self {
+        let mut dynamic = match &self.perturbation {
+            None => Self::new(self.kind, plane, self.f.clone()),
+            Some(perturbation) => Self::new_perturbed(&perturbation.center, plane, self.f.clone()),
+        };
+        dynamic.roots = self.roots.clone();
+        dynamic.derivative = self.derivative;
+        dynamic
+    }
+
+    /** a dynamic on another plane on the same pixel grid (like a pan or a larger window), which takes over the
+     *  state of the coordinates in common
+     *  - only the newly exposed coordinates are simulated from the start, the others resume once they caught up with
+     *    the iteration of this dynamic
+     *  - a perturbed dynamic keeps its reference orbit, instead of moving it to the center of the plane
+     *  - None if the pixels don't coincide, nothing is in common, or this dynamic is still resuming itself
+     **/
+    pub fn reuse(&self, plane: Plane<T>) -> Option<Self> {
+        let (dx, dy) = plane.grid_offset(&self.plane)?;
+        /* the rows and columns in common, in the coordinates of this plane */
+        let common = |offset: i32, old: usize, new: usize| {
+            let (offset, old, new) = (offset as i64, old as i64, new as i64);
+            (-offset).max(0)..old.min(new - offset)
+        };
+        let (columns, rows) = (common(dx, self.plane.width, plane.width), common(dy, self.plane.height, plane.height));
+        if columns.is_empty() || rows.is_empty() || self.i == 0 || self.resumed > self.i {
+            return None;
+        }
+
+        let mut dynamic = Self::new(self.kind, plane, self.f.clone());
+        dynamic.perturbation = self.perturbation.as_ref().map(|perturbation| perturbation.with_plane(&plane));
+        dynamic.roots = self.roots.clone();
+        dynamic.derivative = self.derivative;
+        dynamic.resumed = self.i;
+        for y0 in rows {
+            let (y, y1) = (y0 as usize, (y0 + dy as i64) as usize);
+            for x0 in columns.clone() {
+                let (x, x1) = (x0 as usize, (x0 + dx as i64) as usize);
+                dynamic.z[y1][x1] = self.z[y][x];
+                dynamic.dz[y1][x1] = self.dz[y][x];
+                dynamic.d[y1][x1] = self.d[y][x];
+                dynamic.z_saved[y1][x1] = self.z_saved[y][x];
+                dynamic.period[y1][x1] = match self.period[y][x] {
+                    0 if self.d[y][x] == D_MAX => SKIPPED,
+                    period => period,
+                };
+                if let (Some(reused), Some(perturbation)) = (dynamic.perturbation.as_mut(), &self.perturbation) {
+                    reused.delta[y1][x1] = perturbation.delta[y][x];
+                    reused.n[y1][x1] = perturbation.n[y][x];
+                }
+            }
+        }
+        Some(dynamic)
+    }
+
+    /** the index of the root, which the Newton method converged to at a coordinate **/
+    pub fn root(&self, x: usize, y: usize) -> Option<usize> {
+        if self.d[y][x] == D_MAX {
+            return None;
+        }
+        let z = self.z[y][x];
+        self.roots.iter()
+            .map(|&root| (z - root).norm())
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            .map(|(k, _)| k)
     }
 }
 
@@ -22,9 +120,8 @@ impl HolomorphicDynamic {
 pub struct Juliaset;
 
 impl Juliaset {
-    pub fn new(plane: Plane, f: impl HolomorphicFn + 'static) -> HolomorphicDynamic {
-        let z0 = plane.c();
-        HolomorphicDynamic::new(plane, f, z0)
+    pub fn new<T: Precision>(plane: Plane<T>, f: impl HolomorphicFn<T> + 'static) -> HolomorphicDynamic<T> {
+        HolomorphicDynamic::new(DynamicKind::Juliaset, plane, Box::new(f))
     }
 }
 
@@ -32,8 +129,38 @@ impl Juliaset {
 pub struct Mandelbrot;
 
 impl Mandelbrot {
-    pub fn new(plane: Plane, f: impl HolomorphicFn + 'static) -> HolomorphicDynamic {
-        let z0 = vec![vec![Complex::new(0.0,0.0); plane.width]; plane.height];
-        HolomorphicDynamic::new(plane, f, z0)
+    pub fn new<T: Precision>(plane: Plane<T>, f: impl HolomorphicFn<T> + 'static) -> HolomorphicDynamic<T> {
+        HolomorphicDynamic::new(DynamicKind::Mandelbrot, plane, Box::new(f))
+    }
+
+    /** deep zoom around an arbitrary precision center, the plane is relative to it **/
+    pub fn perturbed<T: Precision>(center: &BigComplex, plane: Plane<T>) -> HolomorphicDynamic<T> {
+        let f = |z: Complex<T>, c: Complex<T>| { z*z+c };
+        HolomorphicDynamic::new_perturbed(center, plane, Box::new(f))
+    }
+}
+
+
+pub struct Newton;
+
+impl Newton {
+    /** the Newton method z - a·p(z)/p'(z) for the polynomial p(z) = (z-r_1)·…·(z-r_n) given by its roots
+     *  - the relaxation a = 1 is the classical Newton method, other values distort the basins of the roots
+     **/
+    pub fn dynamic<T: Precision>(plane: Plane<T>, roots: Vec<Complex<T>>, relaxation: Complex<T>) -> HolomorphicDynamic<T> {
+        let attractors = roots.clone();
+        let f = move |z: Complex<T>, _c| {
+            /* p'(z)/p(z) = Σ 1/(z-r_k) */
+            let s = attractors.iter().fold(Complex::new(T::zero(), T::zero()), |s, &root| s + (z - root).inv());
+            if s.re.is_nan() || s.im.is_nan() {
+                /* z is exactly a root */
+                z
+            } else {
+                z - relaxation / s
+            }
+        };
+        let mut dynamic = HolomorphicDynamic::new(DynamicKind::Newton, plane, Box::new(f));
+        dynamic.roots = roots;
+        dynamic
     }
 }
@@ -1,21 +1,41 @@
-use num::complex::Complex32;
+use num::Complex;
+use super::precision::Precision;
 
 
-pub trait HolomorphicFn: Fn(Complex32, Complex32) -> Complex32 + Sync {
-    fn clone_box(&self) -> Box<dyn HolomorphicFn>;
+pub trait HolomorphicFn<T: Precision = f32>: Fn(Complex<T>, Complex<T>) -> Complex<T> + Send + Sync {
+    fn clone_box(&self) -> Box<dyn HolomorphicFn<T>>;
+
+    /** the partial derivative ∂f/∂z, approximated by a central difference **/
+    fn df_dz(&self, z: Complex<T>, c: Complex<T>) -> Complex<T> {
+        let h = difference_step(z);
+        (self(z+h, c) - self(z-h, c)) / (h+h)
+    }
+
+    /** the partial derivative ∂f/∂c, approximated by a central difference **/
+    fn df_dc(&self, z: Complex<T>, c: Complex<T>) -> Complex<T> {
+        let h = difference_step(c);
+        (self(z, c+h) - self(z, c-h)) / (h+h)
+    }
+}
+
+/** balances the truncation error against the rounding error of a central difference around w **/
+fn difference_step<T: Precision>(w: Complex<T>) -> Complex<T> {
+    let scale = w.norm().max(T::one());
+    Complex::new(T::epsilon().cbrt() * scale, T::zero())
 }
 
-impl<F> HolomorphicFn for F
+impl<T, F> HolomorphicFn<T> for F
 where
-    F: Fn(Complex32, Complex32) -> Complex32 + Sync + 'static + Clone,
+    T: Precision,
+    F: Fn(Complex<T>, Complex<T>) -> Complex<T> + Send + Sync + 'static + Clone,
 {
-    fn clone_box(&self) -> Box<dyn HolomorphicFn + 'static>
+    fn clone_box(&self) -> Box<dyn HolomorphicFn<T> + 'static>
     {
         Box::new(self.clone())
     }
 }
 
-impl Clone for Box<dyn HolomorphicFn> {
+impl<T: Precision> Clone for Box<dyn HolomorphicFn<T>> {
     fn clone(&self) -> Self {
         (**self).clone_box()
     }
@@ -0,0 +1,424 @@
+use num::Complex;
+use std::fmt;
+use super::{precision::Precision, r#fn::HolomorphicFn};
+
+
+/** Maximal number of intermediate results, which limits the nesting of a formula **/
+const MAX_DEPTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Function {
+    Sin, Cos, Tan,
+    Sinh, Cosh, Tanh,
+    Exp, Ln, Sqrt,
+}
+
+const FUNCTIONS: [(&str, Function); 10] = [
+    ("sin", Function::Sin), ("cos", Function::Cos), ("tan", Function::Tan),
+    ("sinh", Function::Sinh), ("cosh", Function::Cosh), ("tanh", Function::Tanh),
+    ("exp", Function::Exp), ("ln", Function::Ln), ("log", Function::Ln), ("sqrt", Function::Sqrt),
+];
+
+impl Function {
+    fn apply<T: Precision>(self, w: Complex<T>) -> Complex<T> {
+        match self {
+            Function::Sin => w.sin(),
+            Function::Cos => w.cos(),
+            Function::Tan => w.tan(),
+            Function::Sinh => w.sinh(),
+            Function::Cosh => w.cosh(),
+            Function::Tanh => w.tanh(),
+            Function::Exp => w.exp(),
+            Function::Ln => w.ln(),
+            Function::Sqrt => w.sqrt(),
+        }
+    }
+}
+
+/** Instruction of a stack machine **/
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op<T> {
+    Z,
+    C,
+    Const(Complex<T>),
+    Neg,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    PowI(i32),
+    Pow,
+    Apply(Function),
+}
+
+impl<T: Precision> Op<T> {
+    fn unary(self, a: Complex<T>) -> Complex<T> {
+        match self {
+            Op::Neg => -a,
+            Op::PowI(n) => a.powi(n),
+            Op::Apply(function) => function.apply(a),
+            _ => unreachable!("{:?} is not unary", self),
+        }
+    }
+
+    fn binary(self, a: Complex<T>, b: Complex<T>) -> Complex<T> {
+        match self {
+            Op::Add => a + b,
+            Op::Sub => a - b,
+            Op::Mul => a * b,
+            Op::Div => a / b,
+            Op::Pow => a.powc(b),
+            _ => unreachable!("{:?} is not binary", self),
+        }
+    }
+}
+
+
+/** A holomorphic function f(z, c) compiled from a string like "z^3 + c", "sin(z)*c" or "z*z + 0.285 + 0.01i"
+ *  - operators: + - * / ^ and parentheses
+ *  - variables: z, c and the constants i, pi, e
+ *  - numbers: 2, 0.285, 1.5e-3, imaginary with a trailing i like 0.01i
+ *  - functions: sin, cos, tan, sinh, cosh, tanh, exp, ln (or log), sqrt
+ *
+ *  The formula is compiled to postfix instructions with folded constants, so evaluating it doesn't allocate.
+ **/
+#[derive(Clone, Debug)]
+pub struct Formula<T: Precision = f32> {
+    source: String,
+    ops: Vec<Op<T>>,
+}
+
+impl<T: Precision> Formula<T> {
+    /** compiles a formula, errors point to the position of the problem **/
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source).map_err(|(position, message)| error(source, position, &message))?;
+        let mut parser = Parser {tokens: &tokens, next: 0, ops: Vec::new(), depth: 0, max_depth: 0};
+        parser.expression().map_err(|(position, message)| error(source, position, &message))?;
+        let (position, token) = parser.peek();
+        if token != Token::End {
+            return Err(error(source, position, &format!("unexpected {}, expected an operator", token)));
+        }
+        Ok(Self {source: source.trim().to_string(), ops: parser.ops})
+    }
+
+    /** f(z, c) **/
+    pub fn eval(&self, z: Complex<T>, c: Complex<T>) -> Complex<T> {
+        let mut stack = [Complex::new(T::zero(), T::zero()); MAX_DEPTH];
+        let mut top = 0;
+        for &op in &self.ops {
+            match op {
+                Op::Z => { stack[top] = z; top += 1; }
+                Op::C => { stack[top] = c; top += 1; }
+                Op::Const(w) => { stack[top] = w; top += 1; }
+                Op::Neg | Op::PowI(_) | Op::Apply(_) => { stack[top-1] = op.unary(stack[top-1]); }
+                Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Pow => {
+                    top -= 1;
+                    stack[top-1] = op.binary(stack[top-1], stack[top]);
+                }
+            }
+        }
+        stack[0]
+    }
+
+    /** whether the formula is z² + c, which allows deep zooms by perturbation **/
+    pub fn is_quadratic(&self) -> bool {
+        ["z^2+c", "c+z^2", "z*z+c", "c+z*z"].iter()
+            .any(|quadratic| Self::parse(quadratic).is_ok_and(|quadratic| quadratic.ops == self.ops))
+    }
+
+    pub fn into_fn(self) -> impl HolomorphicFn<T> {
+        move |z, c| self.eval(z, c)
+    }
+}
+
+impl<T: Precision> fmt::Display for Formula<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/** the source with a marker below the position of the problem **/
+fn error(source: &str, position: usize, message: &str) -> String {
+    format!("{}\n{}^ {}", source, " ".repeat(position), message)
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<'a> {
+    Number(f64),
+    Imaginary(f64),
+    Name(&'a str),
+    Operator(char),
+    Open,
+    Close,
+    End,
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(x) => write!(f, "number {}", x),
+            Token::Imaginary(x) => write!(f, "number {}i", x),
+            Token::Name(name) => write!(f, "'{}'", name),
+            Token::Operator(op) => write!(f, "'{}'", op),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::End => write!(f, "end of formula"),
+        }
+    }
+}
+
+type Positioned<'a> = (usize, Token<'a>);
+type ParseError = (usize, String);
+
+/** splits the source into tokens and their positions in characters **/
+fn tokenize(source: &str) -> Result<Vec<Positioned<'_>>, ParseError> {
+    let chars = source.char_indices().collect::<Vec<_>>();
+    let byte = |k: usize| chars.get(k).map_or(source.len(), |&(b, _)| b);
+    let is = |k: usize, f: fn(char) -> bool| chars.get(k).is_some_and(|&(_, ch)| f(ch));
+
+    let mut tokens = Vec::new();
+    let mut k = 0;
+    while k < chars.len() {
+        let (_, ch) = chars[k];
+        let start = k;
+        if ch.is_whitespace() {
+            k += 1;
+            continue;
+        } else if ch.is_ascii_digit() || ch == '.' {
+            while is(k, |ch| ch.is_ascii_digit() || ch == '.') {
+                k += 1;
+            }
+            /* an exponent like e-3, but not the constant e */
+            let sign = usize::from(is(k+1, |ch| ch == '+' || ch == '-'));
+            if is(k, |ch| ch == 'e' || ch == 'E') && is(k+1+sign, |ch| ch.is_ascii_digit()) {
+                k += 1 + sign;
+                while is(k, |ch| ch.is_ascii_digit()) {
+                    k += 1;
+                }
+            }
+            let number = &source[byte(start)..byte(k)];
+            let x = number.parse::<f64>().map_err(|_| (start, format!("invalid number '{}'", number)))?;
+            if is(k, |ch| ch == 'i') && !is(k+1, char::is_alphanumeric) {
+                k += 1;
+                tokens.push((start, Token::Imaginary(x)));
+            } else {
+                tokens.push((start, Token::Number(x)));
+            }
+        } else if ch.is_alphabetic() {
+            while is(k, |ch| ch.is_alphanumeric() || ch == '_') {
+                k += 1;
+            }
+            tokens.push((start, Token::Name(&source[byte(start)..byte(k)])));
+        } else if "+-*/^".contains(ch) {
+            k += 1;
+            tokens.push((start, Token::Operator(ch)));
+        } else if ch == '(' {
+            k += 1;
+            tokens.push((start, Token::Open));
+        } else if ch == ')' {
+            k += 1;
+            tokens.push((start, Token::Close));
+        } else {
+            return Err((start, format!("unexpected character '{}'", ch)));
+        }
+    }
+    tokens.push((chars.len(), Token::End));
+    Ok(tokens)
+}
+
+
+/** Recursive descent parser, which emits postfix instructions
+ *  > expression = term (('+' | '-') term)*
+ *  > term       = unary (('*' | '/') unary)*
+ *  > unary      = ('-' | '+') unary | power
+ *  > power      = atom ('^' unary)?
+ *  > atom       = number | name | name '(' expression ')' | '(' expression ')'
+ **/
+struct Parser<'a, T> {
+    tokens: &'a [Positioned<'a>],
+    next: usize,
+    ops: Vec<Op<T>>,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<'a, T: Precision> Parser<'a, T> {
+    fn peek(&self) -> Positioned<'a> {
+        self.tokens[self.next]
+    }
+
+    fn advance(&mut self) -> Positioned<'a> {
+        let token = self.tokens[self.next];
+        self.next = (self.next + 1).min(self.tokens.len() - 1);
+        token
+    }
+
+    /** appends an instruction, folding it into the preceding constants if possible **/
+    fn emit(&mut self, position: usize, op: Op<T>) -> Result<(), ParseError> {
+        match op {
+            Op::Z | Op::C | Op::Const(_) => {
+                self.depth += 1;
+                self.max_depth = self.max_depth.max(self.depth);
+                if self.max_depth > MAX_DEPTH {
+                    return Err((position, format!("nested too deeply, at most {} intermediate results are supported", MAX_DEPTH)));
+                }
+                self.ops.push(op);
+            }
+            Op::Neg | Op::PowI(_) | Op::Apply(_) => match self.ops.last_mut() {
+                Some(Op::Const(a)) => *a = op.unary(*a),
+                _ => self.ops.push(op),
+            },
+            Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Pow => {
+                self.depth -= 1;
+                match self.ops[..] {
+                    [.., Op::Const(a), Op::Const(b)] => {
+                        self.ops.pop();
+                        *self.ops.last_mut().unwrap() = Op::Const(op.binary(a, b));
+                    }
+                    _ => self.ops.push(op),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn constant(re: f64, im: f64) -> Op<T> {
+        Op::Const(Complex::new(T::from_f64(re).unwrap_or(T::nan()), T::from_f64(im).unwrap_or(T::nan())))
+    }
+
+    fn expression(&mut self) -> Result<(), ParseError> {
+        self.term()?;
+        while let (position, Token::Operator(op @ ('+' | '-'))) = self.peek() {
+            self.advance();
+            self.term()?;
+            self.emit(position, if op == '+' { Op::Add } else { Op::Sub })?;
+        }
+        Ok(())
+    }
+
+    fn term(&mut self) -> Result<(), ParseError> {
+        self.unary()?;
+        while let (position, Token::Operator(op @ ('*' | '/'))) = self.peek() {
+            self.advance();
+            self.unary()?;
+            self.emit(position, if op == '*' { Op::Mul } else { Op::Div })?;
+        }
+        Ok(())
+    }
+
+    fn unary(&mut self) -> Result<(), ParseError> {
+        match self.peek() {
+            (position, Token::Operator('-')) => {
+                self.advance();
+                self.unary()?;
+                self.emit(position, Op::Neg)
+            }
+            (_, Token::Operator('+')) => {
+                self.advance();
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<(), ParseError> {
+        self.atom()?;
+        if let (position, Token::Operator('^')) = self.peek() {
+            self.advance();
+            self.unary()?;
+            /* real integer exponents are faster and exact */
+            if let Some(&Op::Const(n)) = self.ops.last() {
+                let real = n.im.is_zero();
+                let n = n.re.to_f64().unwrap_or(f64::NAN);
+                let integer = real && n.fract() == 0.0 && n.abs() <= i32::MAX as f64;
+                if integer && self.ops.len() >= 2 && !matches!(self.ops[self.ops.len()-2], Op::Const(_)) {
+                    self.ops.pop();
+                    self.depth -= 1;
+                    return self.emit(position, Op::PowI(n as i32));
+                }
+            }
+            self.emit(position, Op::Pow)?;
+        }
+        Ok(())
+    }
+
+    fn atom(&mut self) -> Result<(), ParseError> {
+        match self.advance() {
+            (position, Token::Number(x)) => self.emit(position, Self::constant(x, 0.0)),
+            (position, Token::Imaginary(x)) => self.emit(position, Self::constant(0.0, x)),
+            (position, Token::Open) => {
+                self.expression()?;
+                self.close(position)
+            }
+            (position, Token::Name(name)) => {
+                if let Some(&(_, function)) = FUNCTIONS.iter().find(|(known, _)| *known == name) {
+                    match self.advance() {
+                        (open, Token::Open) => {
+                            self.expression()?;
+                            self.close(open)?;
+                            self.emit(position, Op::Apply(function))
+                        }
+                        (position, token) => Err((position, format!("unexpected {}, expected '(' after {}", token, name))),
+                    }
+                } else {
+                    let op = match name {
+                        "z" => Op::Z,
+                        "c" => Op::C,
+                        "i" => Self::constant(0.0, 1.0),
+                        "pi" => Self::constant(std::f64::consts::PI, 0.0),
+                        "e" => Self::constant(std::f64::consts::E, 0.0),
+                        _ => {
+                            let functions = FUNCTIONS.iter().map(|(known, _)| *known).collect::<Vec<_>>().join(", ");
+                            return Err((position, format!("unknown name '{}', use z, c, i, pi, e or one of {}", name, functions)));
+                        }
+                    };
+                    self.emit(position, op)
+                }
+            }
+            (position, token) => Err((position, format!("unexpected {}, expected a number, variable, function or '('", token))),
+        }
+    }
+
+    /** consumes the ')' matching an '(' **/
+    fn close(&mut self, open: usize) -> Result<(), ParseError> {
+        match self.advance() {
+            (_, Token::Close) => Ok(()),
+            (position, Token::End) => Err((position, format!("missing ')' for the '(' at position {}", open))),
+            (position, token) => Err((position, format!("unexpected {}, expected an operator or ')'", token))),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str, z: Complex<f64>) -> Complex<f64> {
+        Formula::<f64>::parse(source).unwrap().eval(z, Complex::new(0.0, 0.0))
+    }
+
+    /** the position of the marker below the source **/
+    fn error_position(source: &str) -> usize {
+        let message = Formula::<f64>::parse(source).unwrap_err();
+        message.lines().nth(1).and_then(|marker| marker.find('^')).unwrap()
+    }
+
+    #[test]
+    fn complex_exponents() {
+        let z = Complex::new(0.7, -1.3);
+        for (source, exponent) in [("z^i", Complex::new(0.0, 1.0)), ("z^(1i)", Complex::new(0.0, 1.0)), ("z^(2+1i)", Complex::new(2.0, 1.0)), ("z^(0.5)", Complex::new(0.5, 0.0))] {
+            assert!((eval(source, z) - z.powc(exponent)).norm() < 1e-12, "{}", source);
+        }
+        assert_eq!(eval("z^3", z), z.powi(3));
+        assert_eq!(eval("z^(-2)", z), z.powi(-2));
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error_position("z +"), 3);
+        assert_eq!(error_position("(z"), 2);
+        assert_eq!(error_position("foo(z)"), 0);
+    }
+}
@@ -0,0 +1,51 @@
+use super::{dynamic::HolomorphicDynamic, precision::Precision, simulation::D_MAX};
+
+
+/** Cumulative distribution of the iterations of divergence in a view
+ *  > cdf[k] = fraction of the diverged coordinates with d < d_min + k
+ **/
+pub struct Histogram {
+    d_min: i32,
+    cdf: Vec<f64>,
+}
+
+impl Histogram {
+    /** the position in [0, 1] of a smooth iteration of divergence, so that the positions of all coordinates are
+     *  equally distributed
+     *  - within an iteration, the fraction of the smooth iteration interpolates between its neighbours
+     **/
+    pub fn position(&self, smooth_d: f64) -> f64 {
+        let last = self.cdf.len() - 1;
+        let k = smooth_d.floor() - self.d_min as f64;
+        if k.is_nan() || k < 0.0 {
+            return 0.0;
+        }
+        if k >= last as f64 {
+            return self.cdf[last];
+        }
+        let (k, f) = (k as usize, smooth_d.fract());
+        self.cdf[k] + (self.cdf[k + 1] - self.cdf[k]) * f
+    }
+}
+
+impl<T: Precision> HolomorphicDynamic<T> {
+    /** the histogram of the iterations of divergence, coordinates which have not diverged (yet) are ignored **/
+    pub fn histogram(&self) -> Histogram {
+        let diverged = || self.d.iter().flatten().copied().filter(|&d| d != D_MAX);
+        let (d_min, d_max) = diverged().fold((i32::MAX, i32::MIN), |(min, max), d| (min.min(d), max.max(d)));
+        if d_min > d_max {
+            return Histogram {d_min: 0, cdf: vec![0.0]};
+        }
+
+        let mut counts = vec![0_u64; (d_max - d_min) as usize + 1];
+        for d in diverged() {
+            counts[(d - d_min) as usize] += 1;
+        }
+        let total = counts.iter().sum::<u64>() as f64;
+        let cdf = [0].iter().chain(&counts).scan(0, |sum, &count| {
+            *sum += count;
+            Some(*sum as f64 / total)
+        }).collect();
+        Histogram {d_min, cdf}
+    }
+}
@@ -1,6 +1,23 @@
+pub mod bigfixed;
+pub mod buddhabrot;
+pub mod checkpoint;
+pub mod distance;
 pub mod dynamic;
+pub mod formula;
+pub mod histogram;
 pub mod r#fn;
+pub mod periodicity;
+pub mod perturbation;
 pub mod plane;
+pub mod portrait;
+pub mod precision;
+pub mod session;
 pub mod simulation;
+pub mod smooth;
+pub mod supersampling;
+pub mod tiles;
+pub mod viewport;
 pub mod visualize;
+pub mod worker;
+pub mod zoom;
 pub mod test;
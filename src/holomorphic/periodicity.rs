@@ -0,0 +1,47 @@
+use num::Complex;
+use super::{precision::Precision, r#fn::HolomorphicFn};
+
+
+/** Points of the main cardioid and the period 2 bulb take the most iterations, but can be recognized directly
+ *  - only valid for the Mandelbrot set of z² + c
+ *  - returns the period of the attracting cycle or 0, if c is outside of both
+ **/
+pub fn cardioid_or_bulb<T: Precision>(c: Complex<T>) -> i32 {
+    let quarter = T::from_f32(0.25).unwrap_or(T::nan());
+    let sixteenth = quarter * quarter;
+    let q = (c.re - quarter).powi(2) + c.im.powi(2);
+    if q * (q + (c.re - quarter)) <= quarter * c.im.powi(2) {
+        1
+    } else if (c.re + T::one()).powi(2) + c.im.powi(2) <= sixteenth {
+        2
+    } else {
+        0
+    }
+}
+
+/** whether f(z, c) = z² + c, judged by a few samples **/
+pub fn is_quadratic<T: Precision>(f: &dyn HolomorphicFn<T>) -> bool {
+    let samples = [(0.5, -0.25, 0.1, 0.3), (-1.5, 2.0, -0.7, -0.2), (0.0, 0.0, 1.0, 1.0)];
+    samples.iter().all(|&(z_re, z_im, c_re, c_im)| {
+        let z = Complex::new(T::from_f32(z_re).unwrap_or(T::nan()), T::from_f32(z_im).unwrap_or(T::nan()));
+        let c = Complex::new(T::from_f32(c_re).unwrap_or(T::nan()), T::from_f32(c_im).unwrap_or(T::nan()));
+        let expected = z*z + c;
+        (f(z, c) - expected).norm() <= T::epsilon() * (T::one() + expected.norm()) * T::from_f32(8.0).unwrap_or(T::nan())
+    })
+}
+
+/** Brent's cycle detection: z is saved at the iterations 2^k and compared to the following ones
+ *  - returns the iteration of the saved z, which is compared at iteration i
+ **/
+pub fn saved_iteration(i: i32) -> i32 {
+    if i <= 1 {
+        0
+    } else {
+        1 << (31 - (i-1).leading_zeros())
+    }
+}
+
+/** the distance, below which a repeated z is considered a cycle instead of a coincidence **/
+pub fn cycle_tolerance<T: Precision>() -> T {
+    T::epsilon() * T::from_f32(64.0).unwrap_or(T::nan())
+}
@@ -0,0 +1,151 @@
+use num::Complex;
+use rayon::prelude::*;
+use super::{bigfixed::BigComplex, dynamic::{DynamicKind, HolomorphicDynamic}, r#fn::HolomorphicFn, plane::Plane, precision::Precision, simulation::{BOUND, D_MAX}};
+
+
+/** Precision of the reference orbit beyond the pixel size **/
+const GUARD_BITS: u32 = 64;
+
+/** Deep zoom state for the Mandelbrot set of f(z, c) = z² + c
+ *
+ *  Only a single reference orbit Z_n of the center c_ref is iterated in arbitrary precision. Each pixel at
+ *  c = c_ref + δc then iterates the difference δ_n = z_n - Z_n to the reference in T (perturbation theory):
+ *  > δ_n+1 = 2·Z_n·δ_n + δ_n² + δc
+ *
+ *  Since δc is tiny but well representable, zooms are limited by the exponent range of T instead of its mantissa.
+ *
+ *  When |Z_n + δ_n| < |δ_n|, the pixel got closer to 0 than to the reference and δ loses its precision (a „glitch“).
+ *  Such pixels are rebased to the start of the reference orbit: δ := Z_n + δ_n, n := 0.
+ *  The same happens, when the reference orbit escaped before the pixel.
+ **/
+#[derive(Clone)]
+pub struct Perturbation<T: Precision = f64> {
+    pub center: BigComplex,           // c_ref, the plane is relative to it
+    pub reference: Vec<Complex<T>>,   // Z_0..Z_n
+    reference_z: BigComplex,          // Z_n in arbitrary precision
+    reference_escaped: bool,
+    pub delta: Vec<Vec<Complex<T>>>,  // δ of each pixel
+    pub n: Vec<Vec<usize>>,           // index into the reference orbit for each pixel
+}
+
+impl<T: Precision> Perturbation<T> {
+    pub fn new(center: BigComplex, plane: &Plane<T>) -> Self {
+        let bits = Self::bits(plane);
+        let zero = Complex::new(T::zero(), T::zero());
+        Self {
+            center: center.with_bits(bits),
+            reference: vec![zero],
+            reference_z: BigComplex::zero(bits),
+            reference_escaped: false,
+            delta: vec![vec![zero; plane.width]; plane.height],
+            n: vec![vec![0; plane.width]; plane.height],
+        }
+    }
+
+    /** the same reference orbit for another plane relative to center, whose pixels start over **/
+    pub fn with_plane(&self, plane: &Plane<T>) -> Self {
+        let zero = Complex::new(T::zero(), T::zero());
+        Self {
+            center: self.center.clone(),
+            reference: self.reference.clone(),
+            reference_z: self.reference_z.clone(),
+            reference_escaped: self.reference_escaped,
+            delta: vec![vec![zero; plane.width]; plane.height],
+            n: vec![vec![0; plane.width]; plane.height],
+        }
+    }
+
+    /** the precision required to resolve the pixels of a plane **/
+    pub fn bits(plane: &Plane<T>) -> u32 {
+        let pixel_size = plane.pixel_size().to_f64().unwrap_or(1.0).abs();
+        (-pixel_size.log2()).max(0.0).ceil() as u32 + GUARD_BITS
+    }
+
+    /** extends the reference orbit up to Z_i, unless it escaped before **/
+    fn extend_reference(&mut self, i: usize) {
+        while self.reference.len() <= i && !self.reference_escaped {
+            self.reference_z = self.reference_z.square_add(&self.center);
+            let (re, im) = self.reference_z.to_f64();
+            self.reference.push(Complex::new(T::from_f64(re).unwrap_or(T::nan()), T::from_f64(im).unwrap_or(T::nan())));
+            self.reference_escaped = re.hypot(im) > BOUND as f64;
+        }
+    }
+}
+
+
+impl<T: Precision> HolomorphicDynamic<T> {
+    /** a Mandelbrot set around center + the center of the plane, which is given relative to center **/
+    pub(crate) fn new_perturbed(center: &BigComplex, plane: Plane<T>, f: Box<dyn HolomorphicFn<T>>) -> Self {
+        /* the reference is moved to the center of the plane */
+        let offset = plane.center();
+        let plane = plane.translate(-offset);
+        let bits = Perturbation::bits(&plane);
+        let offset = BigComplex::from_f64(offset.re.to_f64().unwrap_or(0.0), offset.im.to_f64().unwrap_or(0.0), bits);
+        let center = center.with_bits(bits).add(&offset);
+
+        let mut dynamic = Self::new(DynamicKind::Mandelbrot, plane, f);
+        dynamic.perturbation = Some(Perturbation::new(center, &plane));
+        dynamic
+    }
+
+    /** the same view, computed relative to a reference orbit at its center (for zooms beyond the precision of T)
+     *  - f is assumed to be z² + c, so this only makes sense for the Mandelbrot set
+     **/
+    pub fn perturbed(&self) -> Self {
+        let origin = BigComplex::zero(0);
+        let center = self.perturbation.as_ref().map_or(&origin, |perturbation| &perturbation.center);
+        let mut dynamic = Self::new_perturbed(center, self.plane, self.f.clone());
+        dynamic.derivative = self.derivative;
+        dynamic
+    }
+
+    /** one iteration of each pixel relative to the reference orbit **/
+    pub(crate) fn step_perturbation(&mut self) {
+        let Some(perturbation) = self.perturbation.as_mut() else { return };
+        let bound = T::from_f32(BOUND).unwrap_or(T::infinity());
+        let two = T::one() + T::one();
+        let i = self.i;
+        let plane = &self.plane;
+        let derivative = self.derivative;
+
+        perturbation.extend_reference(i as usize);
+        let reference = &perturbation.reference;
+        let reference_escaped = perturbation.reference_escaped;
+
+        self.z.par_iter_mut()
+            .zip(self.dz.par_iter_mut())
+            .zip(self.d.par_iter_mut())
+            .zip(self.period.par_iter_mut())
+            .zip(perturbation.delta.par_iter_mut())
+            .zip(perturbation.n.par_iter_mut())
+            .enumerate()
+            .for_each(|(y, (((((z, dz), d), period), delta), n))| {
+                for x in 0..plane.width {
+                    if d[x] != D_MAX || period[x] != 0 {
+                        /* once diverged or known to be interior, we don't further mutate */
+                        continue;
+                    }
+                    let dc = plane.xy_to_c(x, y);
+                    if derivative {
+                        /* the derivative of z^2 + c only depends on the full z */
+                        dz[x] = z[x] * dz[x] * two + Complex::new(T::one(), T::zero());
+                    }
+                    let next_delta = (reference[n[x]] * two + delta[x]) * delta[x] + dc;
+                    let next_n = n[x] + 1;
+                    z[x] = reference[next_n] + next_delta;
+                    if z[x].norm() > bound {
+                        /* this is the iteration of divergence */
+                        d[x] = i;
+                    }
+                    if z[x].norm() < next_delta.norm() || (reference_escaped && next_n + 1 >= reference.len()) {
+                        /* rebase */
+                        delta[x] = z[x];
+                        n[x] = 0;
+                    } else {
+                        delta[x] = next_delta;
+                        n[x] = next_n;
+                    }
+                }
+            });
+    }
+}
@@ -1,35 +1,257 @@
-use num::{complex::Complex32, Complex, ToPrimitive};
-use std::f32::NAN;
+use num::Complex;
 use rayon::prelude::*;
+use super::precision::Precision;
 
 
-/** Rasterized clipping of complex plane **/
+/** Fraction of a pixel, by which the coordinates of planes on the same pixel grid may differ due to rounding **/
+const GRID_TOLERANCE: f64 = 0.01;
+
+/** How a viewport is adapted to a resolution of another aspect ratio
+ *  - fit: the whole viewport stays visible with square pixels, extending one of its axes
+ *  - fill: the viewport is covered with square pixels, cropping one of its axes
+ *  - stretch: the viewport is kept, so pixels become non-square
+ **/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aspect {
+    Fit,
+    Fill,
+    Stretch,
+}
+
+impl Aspect {
+    pub const ALL: [Aspect; 3] = [Aspect::Fit, Aspect::Fill, Aspect::Stretch];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aspect::Fit => "fit",
+            Aspect::Fill => "fill",
+            Aspect::Stretch => "stretch",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL.into_iter().find(|aspect| aspect.name() == name)
+            .ok_or(format!("'{}': unknown aspect, use fit, fill or stretch", name))
+    }
+}
+
+
+/** Rasterized clipping of complex plane
+ *  - the viewport is rotated around its center, so the bounds are those of the unrotated viewport
+ **/
 #[derive(Clone, Copy)]
-pub struct Plane {
+pub struct Plane<T: Precision = f32> {
     /* viewport */
-    pub re_min: f32,
-    pub re_max: f32,
-    pub im_min: f32,
-    pub im_max: f32,
+    pub re_min: T,
+    pub re_max: T,
+    pub im_min: T,
+    pub im_max: T,
+    pub rotation: T,  // counterclockwise in degrees
     /* resolution in pixels */
     pub width: usize,
     pub height: usize,
 }
 
-impl Plane {
+impl<T: Precision> Plane<T> {
+    /** the real part at a (subpixel) column **/
+    fn re(&self, x: T) -> T {
+        self.re_min + (self.re_max - self.re_min) * x / T::from_usize(self.width).unwrap_or(T::nan())
+    }
+
+    /** the imaginary part at a (subpixel) row **/
+    fn im(&self, y: T) -> T {
+        self.im_min + (self.im_max - self.im_min) * y / T::from_usize(self.height).unwrap_or(T::nan())
+    }
+
+    /** the complex number at a (subpixel) coordinate, including the rotation **/
+    fn c_at(&self, x: T, y: T) -> Complex<T> {
+        let c = Complex::new(self.re(x), self.im(y));
+        if self.rotation.is_zero() {
+            return c;
+        }
+        let center = self.center();
+        center + (c - center) * Complex::from_polar(T::one(), self.rotation.to_radians())
+    }
+
     /** the complex number at a coordinate **/
-    pub fn xy_to_c(&self, x: usize, y: usize) -> Complex32 {
-        let re = self.re_min + (self.re_max - self.re_min) * x.to_f32().unwrap_or(NAN) / (self.width as f32);
-        let im = self.im_min + (self.im_max - self.im_min) * y.to_f32().unwrap_or(NAN) / (self.height as f32);
-        Complex::new(re, im)
+    pub fn xy_to_c(&self, x: usize, y: usize) -> Complex<T> {
+        self.c_at(T::from_usize(x).unwrap_or(T::nan()), T::from_usize(y).unwrap_or(T::nan()))
+    }
+
+    /** the (subpixel) coordinate of a complex number, the inverse of xy_to_c **/
+    pub fn c_to_xy(&self, c: Complex<T>) -> (T, T) {
+        let c = if self.rotation.is_zero() {
+            c
+        } else {
+            let center = self.center();
+            center + (c - center) * Complex::from_polar(T::one(), -self.rotation.to_radians())
+        };
+        let x = (c.re - self.re_min) / (self.re_max - self.re_min) * T::from_usize(self.width).unwrap_or(T::nan());
+        let y = (c.im - self.im_min) / (self.im_max - self.im_min) * T::from_usize(self.height).unwrap_or(T::nan());
+        (x, y)
     }
 
     /** the complex number for each coordinate **/
-    pub fn c(&self) -> Vec<Vec<Complex32>> {
+    pub fn c(&self) -> Vec<Vec<Complex<T>>> {
         (0..self.height).into_par_iter().map(|y| {
             (0..self.width).map(|x| {
                 self.xy_to_c(x, y)
             }).collect::<Vec<_>>()
         }).collect::<Vec<_>>()
     }
+
+    /** the complex number in the middle of the viewport **/
+    pub fn center(&self) -> Complex<T> {
+        let two = T::one() + T::one();
+        Complex::new((self.re_min + self.re_max) / two, (self.im_min + self.im_max) / two)
+    }
+
+    /** the distance between neighbouring pixels **/
+    pub fn pixel_size(&self) -> T {
+        (self.re_max - self.re_min) / T::from_usize(self.width).unwrap_or(T::nan())
+    }
+
+    /** the coordinate in this plane of the coordinate (0, 0) of another plane, if all their pixels coincide
+     *  - None if the pixels differ in size or orientation, or are offset by fractions of a pixel
+     **/
+    pub fn grid_offset(&self, other: &Plane<T>) -> Option<(i32, i32)> {
+        let tolerance = T::from_f64(GRID_TOLERANCE).unwrap_or(T::zero());
+        let (width, height) = (T::from_usize(other.width).unwrap_or(T::nan()), T::from_usize(other.height).unwrap_or(T::nan()));
+        let (x, y) = self.c_to_xy(other.c_at(T::zero(), T::zero()));
+        let (dx, dy) = (x.round(), y.round());
+        /* the origin and the opposite ends of both axes of the other plane, which span its grid */
+        let aligned = [(T::zero(), T::zero()), (width, T::zero()), (T::zero(), height)].into_iter().all(|(u, v)| {
+            let (x, y) = self.c_to_xy(other.c_at(u, v));
+            (x - dx - u).abs() < tolerance && (y - dy - v).abs() < tolerance
+        });
+        match aligned {
+            true => Some((dx.to_i32()?, dy.to_i32()?)),
+            false => None,
+        }
+    }
+
+    /** the viewport moved by a complex offset **/
+    pub fn translate(&self, offset: Complex<T>) -> Plane<T> {
+        Plane {
+            re_min: self.re_min + offset.re,
+            re_max: self.re_max + offset.re,
+            im_min: self.im_min + offset.im,
+            im_max: self.im_max + offset.im,
+            ..*self
+        }
+    }
+
+    /** the viewport moved by fractions of a pixel, such that the coordinates sample other positions of their pixels **/
+    pub fn shift(&self, dx: T, dy: T) -> Plane<T> {
+        self.translate(self.c_at(dx, dy) - self.c_at(T::zero(), T::zero()))
+    }
+
+    /** the viewport at t ∈ [0, 1] on the way to end (with its resolution), zooming exponentially
+     *  - the width of the viewport changes by the same factor per step of t
+     *  - all viewports are scaled around the same fixed point, so a nested end view is approached without drifting
+     *  - the rotation changes linearly
+     **/
+    pub fn interpolate(&self, end: &Plane<T>, t: T) -> Plane<T> {
+        let (s0, s1) = (self.re_max - self.re_min, end.re_max - end.re_min);
+        let s = s0 * (s1 / s0).powf(t);
+        /* the fraction of the way from end back to self, linear if the scale doesn't change */
+        let u = if s0 == s1 { T::one() - t } else { (s - s1) / (s0 - s1) };
+        let mix = |a: T, b: T| b + (a - b) * u;
+        Plane {
+            re_min: mix(self.re_min, end.re_min),
+            re_max: mix(self.re_max, end.re_max),
+            im_min: mix(self.im_min, end.im_min),
+            im_max: mix(self.im_max, end.im_max),
+            rotation: self.rotation + (end.rotation - self.rotation) * t,
+            ..*end
+        }
+    }
+
+    /** the viewport scaled by factor around a coordinate, which keeps its position
+     *  - factor < 1 zooms in
+     *  - factor > 1 zooms out
+     **/
+    pub fn zoom(&self, x: i32, y: i32, factor: T) -> Plane<T> {
+        let (x, y) = (T::from_i32(x).unwrap_or(T::nan()), T::from_i32(y).unwrap_or(T::nan()));
+        let (re, im) = (self.re(x), self.im(y));
+        let zoomed = Plane {
+            re_min: re + (self.re_min - re) * factor,
+            re_max: re + (self.re_max - re) * factor,
+            im_min: im + (self.im_min - im) * factor,
+            im_max: im + (self.im_max - im) * factor,
+            ..*self
+        };
+        zoomed.align(self, x, y)
+    }
+
+    /** the viewport moved, such that its content follows a drag by (dx, dy) pixels **/
+    pub fn pan(&self, dx: i32, dy: i32) -> Plane<T> {
+        let (dx, dy) = (T::from_i32(dx).unwrap_or(T::nan()), T::from_i32(dy).unwrap_or(T::nan()));
+        self.translate(self.c_at(T::zero(), T::zero()) - self.c_at(dx, dy))
+    }
+
+    /** the viewport clipped to the rectangle spanned by two coordinates, keeping the resolution **/
+    pub fn select(&self, x0: i32, y0: i32, x1: i32, y1: i32) -> Plane<T> {
+        let re = |x: i32| self.re(T::from_i32(x).unwrap_or(T::nan()));
+        let im = |y: i32| self.im(T::from_i32(y).unwrap_or(T::nan()));
+        let selected = Plane {
+            re_min: re(x0.min(x1)),
+            re_max: re(x0.max(x1)),
+            im_min: im(y0.min(y1)),
+            im_max: im(y0.max(y1)),
+            ..*self
+        };
+        if self.rotation.is_zero() {
+            return selected;
+        }
+        /* the middle of the rectangle becomes the center, around which the selection is rotated */
+        let two = T::one() + T::one();
+        let (x, y) = (T::from_i32(x0 + x1).unwrap_or(T::nan()) / two, T::from_i32(y0 + y1).unwrap_or(T::nan()) / two);
+        selected.translate(self.c_at(x, y) - selected.center())
+    }
+
+    /** the viewport rotated by an angle (counterclockwise in degrees) around a coordinate, which keeps its position **/
+    pub fn rotate(&self, x: i32, y: i32, angle: T) -> Plane<T> {
+        let (x, y) = (T::from_i32(x).unwrap_or(T::nan()), T::from_i32(y).unwrap_or(T::nan()));
+        let rotated = Plane {rotation: self.rotation + angle, ..*self};
+        rotated.align(self, x, y)
+    }
+
+    /** the viewport at another resolution around the same center, adapted to its aspect ratio
+     *  - if the pixels keep their size, the center moves by up to half a pixel to stay on the same pixel grid
+     **/
+    pub fn resize(&self, width: usize, height: usize, aspect: Aspect) -> Plane<T> {
+        let (w, h) = (T::from_usize(width).unwrap_or(T::nan()), T::from_usize(height).unwrap_or(T::nan()));
+        let (re_span, im_span) = (self.re_max - self.re_min, self.im_max - self.im_min);
+        let (re_pixel, im_pixel) = (re_span.abs() / w, im_span.abs() / h);
+        let pixel = match aspect {
+            Aspect::Fit => re_pixel.max(im_pixel),
+            Aspect::Fill => re_pixel.min(im_pixel),
+            Aspect::Stretch => return Plane {width, height, ..*self},
+        };
+        let two = T::one() + T::one();
+        let center = self.center();
+        let re = pixel * w / two * re_span.signum();
+        let im = pixel * h / two * im_span.signum();
+        let resized = Plane {re_min: center.re - re, re_max: center.re + re, im_min: center.im - im, im_max: center.im + im, width, height, ..*self};
+
+        /* the old coordinate (0, 0) at the coordinate (dx, dy), so the pixels in common can be reused */
+        let offset = |new: usize, old: usize| T::from_i64((new as i64 - old as i64).div_euclid(2)).unwrap_or(T::nan());
+        let (dx, dy) = (offset(width, self.width), offset(height, self.height));
+        let aligned = resized.translate(self.c_at(-dx, -dy) - resized.c_at(T::zero(), T::zero()));
+        match aligned.grid_offset(self) {
+            Some(_) => aligned,
+            None => resized,
+        }
+    }
+
+    /** this viewport moved, such that the (subpixel) coordinate shows the same complex number as in the reference
+     *  - after scaling the unrotated viewport, its center and thereby the rotation around it have moved
+     **/
+    fn align(self, reference: &Plane<T>, x: T, y: T) -> Plane<T> {
+        if self.rotation.is_zero() && reference.rotation.is_zero() {
+            return self;
+        }
+        self.translate(reference.c_at(x, y) - self.c_at(x, y))
+    }
 }
@@ -0,0 +1,16 @@
+use num::Complex;
+use crate::coloring::{color_space::ColorSpace, domain_coloring::{phase_portrait, Enhancement}};
+use crate::image::Image;
+use super::{plane::Plane, precision::{to_complex32, Precision}, r#fn::HolomorphicFn};
+
+
+/** The enhanced phase portrait of w = f(z, c) for each coordinate z of the plane, without iterating f **/
+pub fn portrait<T: Precision>(f: &dyn HolomorphicFn<T>, c: Complex<T>, plane: &Plane<T>, enhancement: Enhancement, space: ColorSpace) -> Image {
+    let pixel_size = plane.pixel_size().abs().to_f32().unwrap_or(f32::NAN);
+    Image::from_fn(plane.width, plane.height, |x, y| {
+        let z = plane.xy_to_c(x, y);
+        /* the derivative is only needed for the width of grid lines */
+        let dw = if enhancement.grid { f.df_dz(z, c).norm().to_f32().unwrap_or(f32::NAN) * pixel_size } else { 0.0 };
+        phase_portrait(to_complex32(f(z, c)), dw, enhancement, space)
+    })
+}
@@ -0,0 +1,18 @@
+use num::{complex::Complex32, Complex, Float, FromPrimitive};
+use std::fmt::{Debug, Display};
+
+
+/** Floating point type used for sampling the complex plane
+ *  - f32 is fast and sufficient for the overview
+ *  - f64 allows zooming down to a scale of ~1e-13
+ **/
+pub trait Precision: Float + FromPrimitive + Debug + Display + Send + Sync + 'static {}
+
+impl Precision for f32 {}
+impl Precision for f64 {}
+
+
+/** Complex number reduced to the precision needed for coloring **/
+pub fn to_complex32<T: Precision>(z: Complex<T>) -> Complex32 {
+    Complex::new(z.re.to_f32().unwrap_or(f32::NAN), z.im.to_f32().unwrap_or(f32::NAN))
+}
@@ -0,0 +1,494 @@
+use num::Complex;
+use std::{fmt, fs, path::{Path, PathBuf}};
+use crate::coloring::{color_space::ColorSpace, palette::Palette};
+use super::{bigfixed::{BigComplex, BigFixed}, dynamic::{DynamicKind, HolomorphicDynamic, Juliaset, Mandelbrot, Newton}, formula::Formula, perturbation::Perturbation, plane::Plane, viewport::Viewport, visualize::Coloring};
+
+
+/** Pixel size, below which z² + c is computed relative to an arbitrary precision center (perturbation) **/
+pub const PERTURBATION_PIXEL_SIZE: f64 = 1e-13;
+
+
+/** Everything needed to reproduce a view, stored as human editable text
+ *
+ *  The format is a subset of TOML: one `key = value` per line, where values are "strings", numbers or
+ *  [arrays, of, values]. Complex numbers are strings like "0.285-0.01i". `#` starts a comment.
+ *  > kind = "juliaset"
+ *  > formula = "z^2 + c"
+ *  > julia = "0.285+0.01i"
+ *  > viewport = [-1.6, 1.6, -1.2, 1.2]
+ *  > rotation = 30.0
+ *
+ *  Instead of center and viewport, a view may be given as "RE,IM,SCALE" (see Viewport), e.g. copied from a print:
+ *  > view = "-0.743643887037158704752191506114774,0.131825904205311970493132056385139,1.5e-30"
+ *
+ *  Missing keys keep their default, so sessions can be written by hand.
+ **/
+#[derive(Clone)]
+pub struct Session {
+    pub kind: DynamicKind,
+    pub formula: Formula<f64>,       // f(z, c) of the Mandelbrot and Julia set
+    pub julia: Complex<f64>,         // c of the Julia set
+    pub roots: Vec<Complex<f64>>,    // of the polynomial of the Newton method
+    pub relaxation: Complex<f64>,    // of the Newton method
+    pub center: Option<BigComplex>,  // deep zoom reference, the plane is relative to it
+    pub plane: Plane<f64>,
+    pub iterations: i32,
+    pub coloring: Coloring,
+    pub palette: Palette,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            kind: DynamicKind::Mandelbrot,
+            formula: Formula::parse("z^2 + c").expect("valid formula"),
+            julia: Complex::new(0.0, 1.0),
+            roots: (0..3).map(|k| Complex::from_polar(1.0, k as f64 * 2.0 * std::f64::consts::PI / 3.0)).collect(),
+            relaxation: Complex::new(1.0, 0.0),
+            center: None,
+            plane: Plane {re_min: -2.0, re_max: 0.55, im_min: -1.2, im_max: 1.2, rotation: 0.0, width: 800, height: 600},
+            iterations: 100,
+            coloring: Coloring::Z,
+            palette: Palette::default(),
+        }
+    }
+}
+
+impl Session {
+    /** a new (not yet simulated) dynamic of this view **/
+    pub fn dynamic(&self) -> Result<HolomorphicDynamic<f64>, String> {
+        let mut dynamic = match (self.kind, &self.center) {
+            (DynamicKind::Mandelbrot, Some(center)) => {
+                if !self.formula.is_quadratic() {
+                    return Err(format!("a center is only supported for z^2 + c, not for {}", self.formula));
+                }
+                Mandelbrot::perturbed(center, self.plane)
+            }
+            (DynamicKind::Mandelbrot, None) => Mandelbrot::new(self.plane, self.formula.clone().into_fn()),
+            (DynamicKind::Juliaset, _) => {
+                let (formula, julia) = (self.formula.clone(), self.julia);
+                Juliaset::new(self.plane, move |z, _c| { formula.eval(z, julia) })
+            }
+            (DynamicKind::Newton, _) => Newton::dynamic(self.plane, self.roots.clone(), self.relaxation),
+        };
+        dynamic.derivative = self.coloring == Coloring::Distance;
+        Ok(dynamic)
+    }
+
+    /** this view with the kind, plane and roots of a dynamic, e.g. after the plane was moved relative to a center **/
+    pub fn with_dynamic(&self, dynamic: &HolomorphicDynamic<f64>) -> Self {
+        Self {
+            kind: dynamic.kind,
+            roots: dynamic.roots.clone(),
+            center: dynamic.perturbation.as_ref().map(|perturbation| perturbation.center.clone()),
+            plane: dynamic.plane,
+            ..self.clone()
+        }
+    }
+
+    /** the view of the plane in absolute coordinates **/
+    pub fn viewport(&self) -> Viewport {
+        Viewport::from_plane(&self.plane, self.center.as_ref())
+    }
+
+    /** moves the plane to a view, keeping its resolution
+     *  - deep views of z² + c keep the precision of their center, others are converted to f64
+     **/
+    pub fn set_viewport(&mut self, viewport: &Viewport) {
+        let (width, height) = (self.plane.width, self.plane.height);
+        let deep = viewport.scale / (height as f64) < PERTURBATION_PIXEL_SIZE;
+        if deep && self.kind == DynamicKind::Mandelbrot && self.formula.is_quadratic() {
+            self.center = Some(viewport.center.clone());
+            self.plane = viewport.relative_plane(width, height);
+        } else {
+            self.center = None;
+            self.plane = viewport.plane(width, height);
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::parse_in(text, Path::new(""))
+    }
+
+    /** like parse, but relative paths of palette files are relative to the directory **/
+    fn parse_in(text: &str, directory: &Path) -> Result<Self, String> {
+        let mut session = Self::default();
+        let mut deferred = Deferred {directory: directory.to_path_buf(), ..Deferred::default()};
+        for (n, line) in text.lines().enumerate() {
+            let at_line = |message: String| format!("line {}: {}", n + 1, message);
+            let mut parser = ValueParser {chars: line.char_indices().peekable(), line};
+            let Some(key) = parser.key().map_err(at_line)? else { continue };
+            let value = parser.value().map_err(at_line)?;
+            parser.end().map_err(at_line)?;
+            session.set(key, value, &mut deferred).map_err(|message| at_line(format!("{}: {}", key, message)))?;
+        }
+        session.palette.offset = deferred.palette_offset.unwrap_or(session.palette.offset);
+        session.palette.density = deferred.palette_density.unwrap_or(session.palette.density);
+        session.palette.space = deferred.palette_space.unwrap_or(session.palette.space);
+        if let Some((re, im)) = deferred.center {
+            /* the precision is only known, once the plane is known */
+            let bits = Perturbation::bits(&session.plane);
+            session.center = Some(BigComplex {re: BigFixed::parse(&re, bits)?, im: BigFixed::parse(&im, bits)?});
+        }
+        if let Some(viewport) = deferred.viewport {
+            /* whether it is perturbed depends on the kind, formula and size */
+            session.set_viewport(&viewport);
+        }
+        Ok(session)
+    }
+
+    fn set(&mut self, key: &str, value: Value, deferred: &mut Deferred) -> Result<(), String> {
+        match key {
+            "kind" => self.kind = parse_kind(&value.text()?)?,
+            "formula" => self.formula = Formula::parse(&value.text()?)?,
+            "julia" => self.julia = parse_complex(&value.text()?)?,
+            "roots" => self.roots = value.list()?.into_iter().map(|root| parse_complex(&root.text()?)).collect::<Result<_, _>>()?,
+            "relaxation" => self.relaxation = parse_complex(&value.text()?)?,
+            "center" => {
+                let [re, im] = value.numbers_or_texts::<2>()?;
+                deferred.center = Some((re, im));
+            }
+            "view" => deferred.viewport = Some(Viewport::parse(&value.text()?)?),
+            "viewport" => {
+                let [re_min, re_max, im_min, im_max] = value.numbers::<4>()?;
+                self.plane = Plane {re_min, re_max, im_min, im_max, ..self.plane};
+            }
+            "rotation" => self.plane.rotation = value.numbers::<1>()?[0],
+            "size" => {
+                let [width, height] = value.numbers::<2>()?;
+                if width < 1.0 || height < 1.0 || width.fract() != 0.0 || height.fract() != 0.0 {
+                    return Err(format!("{}x{} is no resolution", width, height));
+                }
+                self.plane = Plane {width: width as usize, height: height as usize, ..self.plane};
+            }
+            "iterations" => {
+                let [iterations] = value.numbers::<1>()?;
+                if iterations < 0.0 || iterations.fract() != 0.0 || iterations > i32::MAX as f64 {
+                    return Err(format!("{} is no number of iterations", iterations));
+                }
+                self.iterations = iterations as i32;
+            }
+            "coloring" => self.coloring = Coloring::parse(&value.text()?)?,
+            "palette" => {
+                let name = value.text()?;
+                /* builtin names or paths of palette files */
+                let builtin = Palette::builtins().iter().any(|palette| palette.name == name);
+                let name = if builtin { name } else { deferred.directory.join(&name).display().to_string() };
+                self.palette = Palette::find(&name)?;
+            }
+            "palette_offset" => deferred.palette_offset = Some(value.numbers::<1>()?[0] as f32),
+            "palette_density" => deferred.palette_density = Some(value.numbers::<1>()?[0] as f32),
+            "palette_space" => deferred.palette_space = Some(ColorSpace::parse(&value.text()?)?),
+            _ => return Err("unknown key".to_string()),
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        Self::parse_in(&text, directory).map_err(|message| format!("{}: {}", path.display(), message))
+    }
+
+    /** writes the session, a palette file with its absolute path, as relative ones are loaded relative to the session **/
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let builtin = Palette::builtins().iter().any(|palette| palette.name == self.palette.name);
+        let name = match fs::canonicalize(&self.palette.name) {
+            Ok(absolute) if !builtin => absolute.display().to_string(),
+            _ => self.palette.name.clone(),
+        };
+        let session = Session {palette: Palette {name, ..self.palette.clone()}, ..self.clone()};
+        fs::write(path, session.to_string()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let roots = self.roots.iter().map(|root| quote(&root.to_string())).collect::<Vec<_>>().join(", ");
+        let plane = &self.plane;
+        writeln!(f, "kind = {}", quote(kind_name(self.kind)))?;
+        writeln!(f, "formula = {}", quote(&self.formula.to_string()))?;
+        writeln!(f, "julia = {}", quote(&self.julia.to_string()))?;
+        writeln!(f, "roots = [{}]", roots)?;
+        writeln!(f, "relaxation = {}", quote(&self.relaxation.to_string()))?;
+        if let Some(center) = &self.center {
+            writeln!(f, "# the viewport is relative to the center")?;
+            writeln!(f, "center = [{}, {}]", quote(&center.re.to_string()), quote(&center.im.to_string()))?;
+        }
+        writeln!(f, "viewport = [{:?}, {:?}, {:?}, {:?}]", plane.re_min, plane.re_max, plane.im_min, plane.im_max)?;
+        if plane.rotation != 0.0 {
+            writeln!(f, "rotation = {:?}", plane.rotation)?;
+        }
+        writeln!(f, "# the same view for sharing: view = {}", quote(&self.viewport().to_string()))?;
+        writeln!(f, "size = [{}, {}]", plane.width, plane.height)?;
+        writeln!(f, "iterations = {}", self.iterations)?;
+        writeln!(f, "coloring = {}", quote(self.coloring.name()))?;
+        writeln!(f, "palette = {}", quote(&self.palette.name))?;
+        writeln!(f, "palette_offset = {:?}", self.palette.offset)?;
+        writeln!(f, "palette_density = {:?}", self.palette.density)?;
+        writeln!(f, "palette_space = {}", quote(self.palette.space.name()))
+    }
+}
+
+
+/** State of parsing with values, which can only be applied once all keys are known **/
+#[derive(Default)]
+struct Deferred {
+    directory: PathBuf,                // of the session file
+    center: Option<(String, String)>,  // its precision depends on the plane
+    viewport: Option<Viewport>,        // replaces center and plane, once the size is known
+    palette_offset: Option<f32>,       // override the palette
+    palette_density: Option<f32>,
+    palette_space: Option<ColorSpace>,
+}
+
+fn kind_name(kind: DynamicKind) -> &'static str {
+    match kind {
+        DynamicKind::Juliaset => "juliaset",
+        DynamicKind::Mandelbrot => "mandelbrot",
+        DynamicKind::Newton => "newton",
+    }
+}
+
+fn parse_kind(name: &str) -> Result<DynamicKind, String> {
+    [DynamicKind::Juliaset, DynamicKind::Mandelbrot, DynamicKind::Newton].into_iter()
+        .find(|&kind| kind_name(kind) == name)
+        .ok_or(format!("'{}': unknown kind, use mandelbrot, juliaset or newton", name))
+}
+
+/** complex numbers like "-0.5+0.866i" are constant formulas **/
+fn parse_complex(s: &str) -> Result<Complex<f64>, String> {
+    let zero = Complex::new(0.0, 0.0);
+    Formula::<f64>::parse(s).map(|constant| constant.eval(zero, zero))
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+
+enum Value {
+    Text(String),
+    Number(f64),
+    List(Vec<Value>),
+}
+
+impl Value {
+    fn text(self) -> Result<String, String> {
+        match self {
+            Value::Text(text) => Ok(text),
+            _ => Err("expected a \"string\"".to_string()),
+        }
+    }
+
+    fn list(self) -> Result<Vec<Value>, String> {
+        match self {
+            Value::List(values) => Ok(values),
+            _ => Err("expected an [array]".to_string()),
+        }
+    }
+
+    /** a single number or an array of N numbers **/
+    fn numbers<const N: usize>(self) -> Result<[f64; N], String> {
+        let values = match self {
+            Value::Number(x) => vec![Value::Number(x)],
+            Value::List(values) => values,
+            Value::Text(_) => vec![],
+        };
+        let numbers = values.into_iter()
+            .map(|value| match value { Value::Number(x) => Some(x), _ => None })
+            .collect::<Option<Vec<_>>>();
+        numbers.and_then(|numbers| numbers.try_into().ok()).ok_or(format!("expected {} numbers", N))
+    }
+
+    /** an array of N numbers, which may be quoted to keep all their digits **/
+    fn numbers_or_texts<const N: usize>(self) -> Result<[String; N], String> {
+        let texts = self.list()?.into_iter()
+            .map(|value| match value { Value::Number(x) => Ok(x.to_string()), value => value.text() })
+            .collect::<Result<Vec<_>, _>>()?;
+        texts.try_into().map_err(|_| format!("expected {} numbers", N))
+    }
+}
+
+/** Parses `key = value # comment` **/
+struct ValueParser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    line: &'a str,
+}
+
+impl<'a> ValueParser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, ch)| ch.is_whitespace()).is_some() {}
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        matches!(self.chars.peek(), None | Some((_, '#')))
+    }
+
+    /** the key, or None for empty lines and comments **/
+    fn key(&mut self) -> Result<Option<&'a str>, String> {
+        if self.at_end() {
+            return Ok(None);
+        }
+        let start = self.chars.peek().map_or(0, |&(k, _)| k);
+        while self.chars.next_if(|(_, ch)| ch.is_alphanumeric() || *ch == '_').is_some() {}
+        let end = self.chars.peek().map_or(self.line.len(), |&(k, _)| k);
+        let key = &self.line[start..end];
+        self.skip_whitespace();
+        if key.is_empty() || self.chars.next_if(|(_, ch)| *ch == '=').is_none() {
+            return Err("expected key = value".to_string());
+        }
+        Ok(Some(key))
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some((_, '"')) => {
+                let mut text = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some((_, '"')) => return Ok(Value::Text(text)),
+                        Some((_, '\\')) => match self.chars.next() {
+                            Some((_, ch @ ('"' | '\\'))) => text.push(ch),
+                            _ => return Err("only \\\" and \\\\ may be escaped".to_string()),
+                        },
+                        Some((_, ch)) => text.push(ch),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+            }
+            Some((_, '[')) => {
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.chars.next_if(|(_, ch)| *ch == ']').is_some() {
+                    return Ok(Value::List(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.chars.next() {
+                        Some((_, ',')) => {}
+                        Some((_, ']')) => return Ok(Value::List(values)),
+                        _ => return Err("expected , or ] (arrays must not span multiple lines)".to_string()),
+                    }
+                }
+            }
+            Some((start, _)) => {
+                while self.chars.next_if(|(_, ch)| !ch.is_whitespace() && !",]#".contains(*ch)).is_some() {}
+                let end = self.chars.peek().map_or(self.line.len(), |&(k, _)| k);
+                let number = &self.line[start..end];
+                number.parse().map(Value::Number).map_err(|e| format!("'{}': {}", number, e))
+            }
+            None => Err("missing value".to_string()),
+        }
+    }
+
+    fn end(&mut self) -> Result<(), String> {
+        if self.at_end() {
+            Ok(())
+        } else {
+            Err("unexpected text after the value".to_string())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /** parses the text of a session and checks, that it is printed the same way again **/
+    fn round_trip(session: &Session) -> Session {
+        let text = session.to_string();
+        let parsed = Session::parse(&text).expect("valid session");
+        assert_eq!(parsed.to_string(), text);
+        parsed
+    }
+
+    #[test]
+    fn round_trip_default() {
+        round_trip(&Session::default());
+    }
+
+    #[test]
+    fn round_trip_julia_set() {
+        let palette = Palette {offset: 3.5, density: 0.125, space: ColorSpace::Oklch, ..Palette::find("fire").expect("builtin palette")};
+        let session = Session {
+            kind: DynamicKind::Juliaset,
+            formula: Formula::parse("z^3 + 0.5*z + c").expect("valid formula"),
+            julia: Complex::new(0.285, -0.01),
+            plane: Plane {re_min: -1.6, re_max: 1.6, im_min: -1.0, im_max: 1.0, rotation: 30.0, width: 320, height: 200},
+            iterations: 1234,
+            coloring: Coloring::Distance,
+            palette,
+            ..Session::default()
+        };
+        let parsed = round_trip(&session);
+        assert_eq!(parsed.kind, DynamicKind::Juliaset);
+        assert_eq!(parsed.julia, session.julia);
+        assert_eq!((parsed.plane.rotation, parsed.plane.width, parsed.plane.height), (30.0, 320, 200));
+        assert_eq!((parsed.iterations, parsed.coloring), (1234, Coloring::Distance));
+        assert_eq!((parsed.palette.name.as_str(), parsed.palette.offset, parsed.palette.density), ("fire", 3.5, 0.125));
+        assert_eq!(parsed.palette.space, ColorSpace::Oklch);
+    }
+
+    #[test]
+    fn round_trip_newton() {
+        let session = Session {
+            kind: DynamicKind::Newton,
+            roots: vec![Complex::new(1.0, 0.0), Complex::new(-0.5, 0.25), Complex::new(0.0, -1.0 / 3.0)],
+            relaxation: Complex::new(1.5, 0.5),
+            coloring: Coloring::Roots,
+            ..Session::default()
+        };
+        let parsed = round_trip(&session);
+        assert_eq!(parsed.roots, session.roots);
+        assert_eq!(parsed.relaxation, session.relaxation);
+    }
+
+    #[test]
+    fn round_trip_center() {
+        let plane = Plane {re_min: -2e-30, re_max: 2e-30, im_min: -1.5e-30, im_max: 1.5e-30, rotation: 0.0, width: 400, height: 300};
+        let bits = Perturbation::bits(&plane);
+        let center = BigComplex {
+            re: BigFixed::parse("-0.743643887037158704752191506114774", bits).expect("valid number"),
+            im: BigFixed::parse("0.131825904205311970493132056385139", bits).expect("valid number"),
+        };
+        let parsed = round_trip(&Session {center: Some(center.clone()), plane, ..Session::default()});
+        assert_eq!(parsed.center, Some(center));
+        assert_eq!(parsed.plane.im_max, plane.im_max);
+    }
+
+    #[test]
+    fn palette_file() {
+        let directory = std::env::temp_dir().join(format!("fractals-session-{}", std::process::id()));
+        fs::create_dir_all(directory.join("sessions")).expect("temporary directory");
+        let palette = directory.join("palette.csv");
+        fs::write(&palette, "0, #000000\n1, #ff8000\n").expect("written");
+        /* the palette relative to the working directory */
+        let depth = std::env::current_dir().expect("working directory").components().count() - 1;
+        let relative = format!("{}{}", "../".repeat(depth), palette.display().to_string().trim_start_matches('/'));
+
+        let session = Session {palette: Palette::find(&relative).expect("valid palette"), ..Session::default()};
+        let path = directory.join("sessions").join("palette.session");
+        session.save(&path).expect("saved");
+        let loaded = Session::load(&path).expect("valid session");
+        assert_eq!(loaded.palette.stops, session.palette.stops);
+        assert_eq!(Path::new(&loaded.palette.name), fs::canonicalize(&palette).expect("existing palette"));
+        fs::remove_dir_all(&directory).expect("removed");
+    }
+
+    #[test]
+    fn view() {
+        let parse = |view: &str| Session::parse(&format!("size = [400, 300]\nview = \"{}\"", view)).expect("valid session");
+        let deep = parse("-0.743643887037158704752191506114774,0.131825904205311970493132056385139,1.5e-30");
+        assert!(deep.center.is_some());
+        assert_eq!(deep.viewport().to_string(), "-0.743643887037158704752191506114774,0.131825904205311970493132056385139,1.5e-30");
+        let shallow = parse("-0.75,0.1,0.5,45");
+        assert!(shallow.center.is_none());
+        assert_eq!((shallow.plane.im_max - shallow.plane.im_min, shallow.plane.rotation), (0.5, 45.0));
+        assert_eq!(shallow.viewport().to_string(), "-0.75,0.1,0.5,45.0");
+    }
+}
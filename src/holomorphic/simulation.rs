@@ -1,6 +1,5 @@
-use num::complex::{Complex32, ComplexFloat};
 use rayon::prelude::*;
-use super::dynamic::HolomorphicDynamic;
+use super::{dynamic::{DynamicKind, HolomorphicDynamic}, periodicity::{cardioid_or_bulb, cycle_tolerance, is_quadratic, saved_iteration}, precision::Precision};
 
 
 pub const D_MAX: i32 = i32::MAX;
@@ -16,7 +15,14 @@ pub const D_MAX: i32 = i32::MAX;
 /// the point is belonging to the Julia set. To prevent overflows, we should set BOUND < D_MAX.nth_root(n), when f(z)
 /// is a polynomial of degree n.
 
-const BOUND: f32 = 1.0e10;
+pub const BOUND: f32 = 1.0e10;
+
+/// CONVERGENCE defines the threshold for the Newton method: once a step moves z by less, it reached a root.
+pub const CONVERGENCE: f32 = 1.0e-5;
+
+/// SKIPPED marks the period of coordinates, which are not simulated, although their orbit is undecided: they are not
+/// refined by a pass of supersampling, or were taken over from another simulation, which is iterations ahead.
+pub(crate) const SKIPPED: i32 = -1;
 
 
 pub trait Simulation {
@@ -24,27 +30,18 @@ pub trait Simulation {
     fn steps(&mut self, iterations: i32);
 }
 
-impl Simulation for HolomorphicDynamic {
+impl<T: Precision> Simulation for HolomorphicDynamic<T> {
     fn step(&mut self) {
         self.i += 1;
-        (self.z, self.d) = (0..self.plane.height).into_par_iter().map(|y| {
-            (0..self.plane.width).map(|x| {
-                if self.d[y][x] != D_MAX {
-                    /* once diverged, we don't further mutate */
-                    (self.z[y][x], self.d[y][x])
-                } else {
-                    let c = self.plane.xy_to_c(x, y);  // TODO: memoize
-                    let z = (self.f)(self.z[y][x], c);
-                    if z.abs() > BOUND {
-                        /* this is the iteration of divergence */
-                        (z, self.i)
-                    } else {
-                        /* not yet diverged */
-                        (z, D_MAX)
-                    }
-                }
-            }).collect::<(Vec<Complex32>, Vec<i32>)>()
-        }).collect::<(Vec<Vec<Complex32>>, Vec<Vec<i32>>)>();
+        if self.perturbation.is_some() {
+            self.step_perturbation();
+        } else {
+            self.step_direct();
+        }
+        if self.i == self.resumed {
+            /* the other coordinates caught up with those taken over from another dynamic */
+            self.period.par_iter_mut().flatten().filter(|period| **period == SKIPPED).for_each(|period| *period = 0);
+        }
     }
 
    fn steps(&mut self, iterations: i32) {
@@ -54,3 +51,58 @@ impl Simulation for HolomorphicDynamic {
     }
 }
 
+
+impl<T: Precision> HolomorphicDynamic<T> {
+    /** one iteration of each pixel in the precision of T **/
+    fn step_direct(&mut self) {
+        let bound = T::from_f32(BOUND).unwrap_or(T::infinity());
+        let convergence = T::from_f32(CONVERGENCE).unwrap_or(T::zero());
+        let (kind, plane, f, i, derivative) = (self.kind, &self.plane, &self.f, self.i, self.derivative);
+        /* cycles are only searched for escape time fractals */
+        let periodic = kind != DynamicKind::Newton;
+        let (tolerance, saved_iteration, save) = (cycle_tolerance::<T>(), saved_iteration(i), (i as u32).is_power_of_two());
+        let cardioid = i == 1 && kind == DynamicKind::Mandelbrot && is_quadratic(f.as_ref());
+        self.z.par_iter_mut()
+            .zip(self.dz.par_iter_mut())
+            .zip(self.d.par_iter_mut())
+            .zip(self.period.par_iter_mut())
+            .zip(self.z_saved.par_iter_mut())
+            .enumerate()
+            .for_each(|(y, ((((z, dz), d), period), z_saved))| {
+                for x in 0..plane.width {
+                    if d[x] != D_MAX || period[x] != 0 {
+                        /* once diverged or known to be interior, we don't further mutate */
+                        continue;
+                    }
+                    let c = plane.xy_to_c(x, y);  // TODO: memoize
+                    if cardioid {
+                        period[x] = cardioid_or_bulb(c);
+                        if period[x] != 0 {
+                            continue;
+                        }
+                    }
+                    if derivative {
+                        /* chain rule, the kinds only differ in dz0 */
+                        dz[x] = f.df_dz(z[x], c) * dz[x] + f.df_dc(z[x], c);
+                    }
+                    let next = f(z[x], c);
+                    let done = match kind {
+                        DynamicKind::Newton => (next - z[x]).norm() < convergence,
+                        DynamicKind::Juliaset | DynamicKind::Mandelbrot => next.norm() > bound,
+                    };
+                    z[x] = next;
+                    if done {
+                        /* this is the iteration of divergence (or convergence) */
+                        d[x] = i;
+                    } else if periodic {
+                        if (z[x] - z_saved[x]).norm() < tolerance {
+                            /* z repeats, so it won't diverge anymore */
+                            period[x] = i - saved_iteration;
+                        } else if save {
+                            z_saved[x] = z[x];
+                        }
+                    }
+                }
+            });
+    }
+}
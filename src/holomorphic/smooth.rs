@@ -0,0 +1,35 @@
+use num::Complex;
+use super::{dynamic::{DynamicKind, HolomorphicDynamic}, precision::Precision, simulation::{BOUND, D_MAX}};
+
+
+impl<T: Precision> HolomorphicDynamic<T> {
+    /** the degree of f near infinity, estimated from |f(z)| ~ |z|^degree **/
+    pub fn degree(&self) -> f64 {
+        let c = self.plane.center();
+        let f_abs = |r: f64| {
+            /* off the real axis, to avoid cancellations of symmetric terms */
+            let z = Complex::from_polar(r, 0.5);
+            let z = Complex::new(T::from_f64(z.re).unwrap_or(T::nan()), T::from_f64(z.im).unwrap_or(T::nan()));
+            (self.f)(z, c).norm().to_f64().unwrap_or(f64::NAN)
+        };
+        let (r1, r2) = (1.0e3, 1.0e4);
+        (f_abs(r2) / f_abs(r1)).ln() / (r2 / r1).ln()
+    }
+
+    /** the iteration of divergence made continuous by renormalization
+     *  - |z| > BOUND happened the first time at iteration d, so |z| ∈ (BOUND, BOUND^degree]
+     *  - therefore log(log|z| / log BOUND) / log(degree) ∈ (0, 1] is the fraction of the iteration, which was
+     *    needed to cross the BOUND
+     *  - for coordinates, which have not diverged (yet), D_MAX is returned
+     *  - the Newton method converges, so its iteration is returned as is
+     **/
+    pub fn smooth_d(&self, x: usize, y: usize, degree: f64) -> f64 {
+        let d = self.d[y][x];
+        let log_z = self.z[y][x].norm().to_f64().unwrap_or(f64::NAN).ln();
+        if d == D_MAX || self.kind == DynamicKind::Newton || !log_z.is_finite() || !degree.is_finite() || degree <= 1.0 {
+            return d as f64;
+        }
+        let log_bound = (BOUND as f64).ln();
+        d as f64 + 1.0 - (log_z / log_bound).ln() / degree.ln()
+    }
+}
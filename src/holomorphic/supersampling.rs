@@ -0,0 +1,133 @@
+use crate::coloring::{color_space::{encode, linear_to_srgb, srgb_to_linear}, palette::Palette, Color, Rgb};
+use crate::image::Image;
+use super::{dynamic::HolomorphicDynamic, precision::Precision, simulation::{Simulation, D_MAX, SKIPPED}, visualize::{Coloring, Visualize}};
+
+
+/** Colors of neighbouring pixels, which differ by more than this in any channel, are refined by adaptive sampling **/
+pub const CONTRAST: u8 = 24;
+
+/** Where the n×n samples of a pixel are placed
+ *  - grid: at the centers of n×n equal cells of the pixel
+ *  - adaptive: like grid, but only for pixels, whose color differs strongly from a neighbour
+ **/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sampling {
+    Grid,
+    Adaptive,
+}
+
+impl Sampling {
+    pub const ALL: [Sampling; 2] = [Sampling::Grid, Sampling::Adaptive];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sampling::Grid => "grid",
+            Sampling::Adaptive => "adaptive",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL.into_iter().find(|sampling| sampling.name() == name)
+            .ok_or(format!("'{}': unknown sampling, use grid or adaptive", name))
+    }
+
+    /** the positions of n×n samples relative to the coordinate of a pixel, which is its center
+     *  - each is a pass of all pixels, so they are the same for all pixels of a pass (random positions would only
+     *    shift the whole grid, not break up its aliasing)
+     **/
+    fn offsets(n: usize) -> Vec<(f64, f64)> {
+        let offset = |k: usize| (k as f64 + 0.5) / n as f64 - 0.5;
+        (0..n*n).map(|k| (offset(k % n), offset(k / n))).collect()
+    }
+}
+
+
+pub trait Supersample {
+    fn supersample(&self, sampling: Sampling, n: usize, coloring: Coloring, palette: &Palette) -> Image;
+}
+
+impl<T: Precision> Supersample for HolomorphicDynamic<T> {
+    /** the visualization of the dynamic with n×n samples per pixel, averaged in linear light
+     *  - each sample is a pass of the whole simulation (up to the iteration of the dynamic) on a shifted plane
+     *  - the sample at the center of the pixel (for odd n) is the dynamic itself
+     **/
+    fn supersample(&self, sampling: Sampling, n: usize, coloring: Coloring, palette: &Palette) -> Image {
+        let image = self.visualize(coloring, palette);
+        let (width, height) = (self.plane.width, self.plane.height);
+        if n <= 1 {
+            return image;
+        }
+
+        let refined = match sampling {
+            Sampling::Adaptive => refined(&image),
+            Sampling::Grid => vec![vec![true; width]; height],
+        };
+        if !refined.iter().flatten().any(|&refined| refined) {
+            return image;
+        }
+        let mut sum = vec![[0.0_f32; 3]; width * height];
+        for (dx, dy) in Sampling::offsets(n) {
+            let pass = if dx == 0.0 && dy == 0.0 {
+                image.clone()
+            } else {
+                self.pass(dx, dy, &refined).visualize(coloring, palette)
+            };
+            for (k, sum) in sum.iter_mut().enumerate() {
+                let color = srgb_to_linear(to_color(pass.pixel(k % width, k / width)));
+                *sum = [0, 1, 2].map(|c| sum[c] + color[c]);
+            }
+        }
+
+        let samples = (n * n) as f32;
+        Image::from_fn(width, height, |x, y| {
+            match refined[y][x] {
+                false => image.pixel(x, y),
+                true => encode(linear_to_srgb(sum[y * width + x].map(|c| c / samples))),
+            }
+        })
+    }
+}
+
+impl<T: Precision> HolomorphicDynamic<T> {
+    /** the simulation on the plane shifted by fractions of a pixel, only for the refined coordinates
+     *  - the others keep the state of this dynamic, so colorings depending on the whole view (like the histogram)
+     *    remain comparable
+     **/
+    fn pass(&self, dx: f64, dy: f64, refined: &[Vec<bool>]) -> Self {
+        let shift = |offset: f64| T::from_f64(offset).unwrap_or(T::nan());
+        let mut pass = self.with_plane(self.plane.shift(shift(dx), shift(dy)));
+        for (y, row) in refined.iter().enumerate() {
+            for (x, _) in row.iter().enumerate().filter(|(_, &refined)| !refined) {
+                pass.z[y][x] = self.z[y][x];
+                pass.dz[y][x] = self.dz[y][x];
+                pass.d[y][x] = self.d[y][x];
+                pass.period[y][x] = match self.period[y][x] {
+                    0 if self.d[y][x] == D_MAX => SKIPPED,
+                    period => period,
+                };
+            }
+        }
+        pass.steps(self.i);
+        pass
+    }
+}
+
+
+/** whether a pixel differs by more than CONTRAST from one of its 4 neighbours **/
+fn refined(image: &Image) -> Vec<Vec<bool>> {
+    let (width, height) = (image.width, image.height);
+    let differs = |a: Rgb, b: Rgb| (0..3).any(|c| a[c].abs_diff(b[c]) > CONTRAST);
+    (0..height).map(|y| {
+        (0..width).map(|x| {
+            let color = image.pixel(x, y);
+            let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+            neighbours.into_iter()
+                .filter(|&(x, y)| x < width && y < height)
+                .any(|(x, y)| differs(color, image.pixel(x, y)))
+        }).collect()
+    }).collect()
+}
+
+fn to_color(rgb: Rgb) -> Color {
+    rgb.map(|c| c as f32 / 255.0)
+}
@@ -0,0 +1,393 @@
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::coloring::palette::Palette;
+use super::{checkpoint, dynamic::HolomorphicDynamic, plane::Plane, session::Session, simulation::Simulation, visualize::Coloring};
+
+
+/** Pixels per side of a tile **/
+pub const TILE_SIZE: usize = 256;
+
+/** Tiles kept in memory by default, each of them takes about 3.7 MB **/
+pub const CACHED_TILES: usize = 64;
+
+/** Size of the pixels of zoom level 0, where a tile covers 4×4 of the complex plane **/
+const LEVEL0_PIXEL: f64 = 4.0 / TILE_SIZE as f64;
+
+/** The deepest zoom level, whose pixels (about 2e-13) are still resolved by f64 without perturbation **/
+const MAX_LEVEL: i32 = 36;
+
+/** A tile on the pixel grid of a zoom level, whose pixels are LEVEL0_PIXEL / 2^level wide
+ *  - the grids of all levels are aligned to 0, tile (x, y) covers the pixels x·TILE_SIZE..(x + 1)·TILE_SIZE
+ *    and y·TILE_SIZE..(y + 1)·TILE_SIZE
+ **/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TileKey {
+    pub level: i32,
+    pub x: i64,
+    pub y: i64,
+}
+
+impl TileKey {
+    pub fn plane(&self) -> Plane<f64> {
+        let size = TILE_SIZE as f64 * pixel_size(self.level);
+        Plane {
+            re_min: self.x as f64 * size,
+            re_max: (self.x + 1) as f64 * size,
+            im_min: self.y as f64 * size,
+            im_max: (self.y + 1) as f64 * size,
+            rotation: 0.0,
+            width: TILE_SIZE,
+            height: TILE_SIZE,
+        }
+    }
+}
+
+/** Tiles of a fractal, which are computed independently and cached, so views visited before are assembled at once
+ *  - the least recently used tiles are evicted from memory, once there are more than the capacity
+ *  - tiles are also kept as checkpoints in a directory (optionally), which outlives the process, until writing
+ *    them fails
+ *  - views have to be snapped to the pixel grid of a zoom level, so zooms change the scale by powers of 2
+ **/
+pub struct Tiles {
+    prototype: HolomorphicDynamic<f64>,  // the fractal, its plane is replaced by those of the tiles
+    session: Session,                    // the fractal for the checkpoints
+    capacity: usize,
+    directory: Option<PathBuf>,          // of the fractal within the directory of the disk cache
+    cached: HashMap<TileKey, (HolomorphicDynamic<f64>, u64)>,  // with the time of their last use
+    clock: u64,
+    disk_error: Option<String>,          // which disabled the disk cache, until it is reported
+}
+
+impl Tiles {
+    /** the tiles of the fractal of a session (its view, iterations and coloring don't matter) **/
+    pub fn new(session: &Session, capacity: usize, directory: Option<PathBuf>) -> Result<Self, String> {
+        let session = Session {
+            center: None,
+            plane: Plane {re_min: 0.0, re_max: 1.0, im_min: 0.0, im_max: 1.0, rotation: 0.0, width: 1, height: 1},
+            iterations: 0,
+            coloring: Coloring::Z,
+            palette: Palette::default(),
+            ..session.clone()
+        };
+        let mut prototype = session.dynamic()?;
+        /* the tiles serve all colorings */
+        prototype.derivative = true;
+        /* as the checkpoints of the tiles tell it, e.g. without the roots of the Newton method */
+        let session = session.with_dynamic(&prototype);
+        /* each fractal gets its own directory, the checkpoints tell, which one it is */
+        let directory = directory.map(|directory| directory.join(format!("{:016x}", fnv1a(&session.to_string()))));
+        Ok(Self {prototype, session, capacity: capacity.max(1), directory, cached: HashMap::new(), clock: 0, disk_error: None})
+    }
+
+    /** the plane moved and scaled by less than half a pixel onto the grid of the nearest zoom level
+     *  - None for rotated planes and those beyond MAX_LEVEL
+     **/
+    pub fn snap(plane: &Plane<f64>) -> Option<Plane<f64>> {
+        let level = level(plane);
+        if plane.rotation != 0.0 || !(plane.pixel_size() > 0.0 && plane.im_max > plane.im_min) || level > MAX_LEVEL {
+            return None;
+        }
+        let pixel = pixel_size(level);
+        let center = plane.center();
+        let re_min = (center.re / pixel - plane.width as f64 / 2.0).round() * pixel;
+        let im_min = (center.im / pixel - plane.height as f64 / 2.0).round() * pixel;
+        Some(Plane {
+            re_min,
+            re_max: re_min + plane.width as f64 * pixel,
+            im_min,
+            im_max: im_min + plane.height as f64 * pixel,
+            ..*plane
+        })
+    }
+
+    /** a tile from memory, at any iteration **/
+    fn cached(&mut self, key: TileKey) -> Option<HolomorphicDynamic<f64>> {
+        self.clock += 1;
+        let (tile, used) = self.cached.get_mut(&key)?;
+        *used = self.clock;
+        Some(tile.clone())
+    }
+
+    /** the error, which disabled the disk cache, only once **/
+    pub fn disk_error(&mut self) -> Option<String> {
+        self.disk_error.take()
+    }
+
+    /** keeps a tile in memory, unless it is iterated further already, evicting the least recently used one **/
+    fn insert(&mut self, key: TileKey, tile: &HolomorphicDynamic<f64>) {
+        self.clock += 1;
+        match self.cached.get_mut(&key) {
+            Some((cached, used)) if cached.i > tile.i => *used = self.clock,
+            _ => {
+                self.cached.insert(key, (tile.clone(), self.clock));
+            }
+        }
+        while self.cached.len() > self.capacity {
+            let Some(&evicted) = self.cached.iter().min_by_key(|(_, (_, used))| *used).map(|(key, _)| key) else { break };
+            self.cached.remove(&evicted);
+        }
+    }
+
+    fn path(&self, key: TileKey) -> Option<PathBuf> {
+        let directory = self.directory.as_ref()?;
+        Some(directory.join(key.level.to_string()).join(format!("{}_{}.tile", key.x, key.y)))
+    }
+}
+
+
+/** the dynamic of a snapped plane at an iteration, assembled from tiles
+ *  - cached tiles are taken over, the missing ones are computed in parallel, starting at the center of the plane
+ *  - tiles iterated further than requested are computed again, but they stay cached instead of the recomputed ones
+ *  - after each batch of tiles, the dynamic is published with the remaining pixels still at their start (black),
+ *    publish returns false to cancel
+ *  - tiles are loaded from and saved to disk without holding the lock, a failure to save is kept for Tiles::disk_error
+ **/
+pub fn assemble(tiles: &Mutex<Tiles>, plane: Plane<f64>, iterations: i32, mut publish: impl FnMut(&HolomorphicDynamic<f64>) -> bool) -> Option<HolomorphicDynamic<f64>> {
+    let level = level(&plane);
+    let pixel = pixel_size(level);
+    let origin = ((plane.re_min / pixel).round() as i64, (plane.im_min / pixel).round() as i64);
+    let size = TILE_SIZE as i64;
+    let (first, last) = ((origin.0.div_euclid(size), origin.1.div_euclid(size)), ((origin.0 + plane.width as i64 - 1).div_euclid(size), (origin.1 + plane.height as i64 - 1).div_euclid(size)));
+    let mut keys = (first.1..=last.1)
+        .flat_map(|y| (first.0..=last.0).map(move |x| TileKey {level, x, y}))
+        .collect::<Vec<_>>();
+    let middle = ((first.0 + last.0) as f64 / 2.0, (first.1 + last.1) as f64 / 2.0);
+    keys.sort_by(|a, b| {
+        let distance = |key: &TileKey| (key.x as f64 - middle.0).hypot(key.y as f64 - middle.1);
+        distance(a).total_cmp(&distance(b))
+    });
+
+    /* the tiles in memory and the paths of the others on disk */
+    let (found, session) = {
+        let mut tiles = tiles.lock().ok()?;
+        let found = keys.into_iter()
+            .map(|key| {
+                let tile = tiles.cached(key);
+                let path = tile.is_none().then(|| tiles.path(key)).flatten();
+                (key, tile, path)
+            })
+            .collect::<Vec<_>>();
+        (found, tiles.session.clone())
+    };
+    let found = found.into_par_iter()
+        .map(|(key, tile, path)| match path {
+            Some(path) => (key, load(&path, &session), true),
+            None => (key, tile, false),
+        })
+        .collect::<Vec<_>>();
+
+    let (mut dynamic, pending) = {
+        let mut tiles = tiles.lock().ok()?;
+        let dynamic = tiles.prototype.with_plane(plane);
+        /* tiles iterated further than requested start over, their divergence can't be undone, and aren't stored */
+        let pending = found.into_iter()
+            .map(|(key, tile, loaded)| {
+                if let Some(tile) = tile.as_ref().filter(|_| loaded) {
+                    tiles.insert(key, tile);
+                }
+                match tile {
+                    Some(tile) if tile.i <= iterations => (key, tile, true),
+                    Some(_) => (key, tiles.prototype.with_plane(key.plane()), false),
+                    None => (key, tiles.prototype.with_plane(key.plane()), true),
+                }
+            })
+            .collect::<Vec<_>>();
+        (dynamic, pending)
+    };
+    dynamic.i = iterations;
+
+    let (ready, missing): (Vec<_>, Vec<_>) = pending.into_iter().partition(|(_, tile, _)| tile.i == iterations);
+    for (key, tile, _) in &ready {
+        copy(&mut dynamic, origin, *key, tile);
+    }
+    if !ready.is_empty() && !missing.is_empty() && !publish(&dynamic) {
+        return None;
+    }
+
+    for batch in missing.chunks(rayon::current_num_threads()) {
+        let computed = batch.par_iter()
+            .map(|(key, tile, stored)| {
+                let mut tile = tile.clone();
+                tile.steps(iterations - tile.i);
+                (*key, tile, *stored)
+            })
+            .collect::<Vec<_>>();
+        let paths = {
+            let mut tiles = tiles.lock().ok()?;
+            computed.iter()
+                .filter(|(_, _, stored)| *stored)
+                .map(|(key, tile, _)| {
+                    tiles.insert(*key, tile);
+                    (tile, tiles.path(*key))
+                })
+                .collect::<Vec<_>>()
+        };
+        for (key, tile, _) in &computed {
+            copy(&mut dynamic, origin, *key, tile);
+        }
+        let saved = paths.into_iter()
+            .filter_map(|(tile, path)| Some((tile, path?)))
+            .try_for_each(|(tile, path)| save(&path, &session, tile));
+        if let Err(message) = saved {
+            /* the tiles are still cached in memory */
+            let mut tiles = tiles.lock().ok()?;
+            tiles.directory = None;
+            tiles.disk_error = Some(message);
+        }
+        if !publish(&dynamic) {
+            return None;
+        }
+    }
+    Some(dynamic)
+}
+
+/** a tile saved before, unless it is missing, unreadable or of another fractal sharing the hash of the directory **/
+fn load(path: &Path, session: &Session) -> Option<HolomorphicDynamic<f64>> {
+    let (saved, tile) = checkpoint::load(path).ok()?;
+    let same = (Session {plane: session.plane, iterations: 0, ..saved}).to_string() == session.to_string();
+    same.then_some(tile)
+}
+
+/** writes a tile as checkpoint of the fractal at its iteration **/
+fn save(path: &Path, session: &Session, tile: &HolomorphicDynamic<f64>) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+    checkpoint::save(path, &Session {iterations: tile.i, ..session.clone()}, tile)
+}
+
+/** the 64 bit FNV-1a hash, which unlike the hashers of std is stable, so the directories of the disk cache are too **/
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/** the zoom level, whose pixels are closest in size to those of a plane **/
+fn level(plane: &Plane<f64>) -> i32 {
+    (LEVEL0_PIXEL / plane.pixel_size()).log2().round().clamp(i32::MIN as f64, i32::MAX as f64) as i32
+}
+
+/** the width of the pixels of a zoom level **/
+fn pixel_size(level: i32) -> f64 {
+    LEVEL0_PIXEL * 2.0_f64.powi(-level)
+}
+
+/** copies the state of the pixels of a tile, which the dynamic covers from its pixel origin of the grid on **/
+fn copy(dynamic: &mut HolomorphicDynamic<f64>, origin: (i64, i64), key: TileKey, tile: &HolomorphicDynamic<f64>) {
+    let size = TILE_SIZE as i64;
+    /* the pixels of the tile in the coordinates of the dynamic */
+    let (x0, y0) = (key.x * size - origin.0, key.y * size - origin.1);
+    let columns = x0.max(0)..(x0 + size).min(dynamic.plane.width as i64);
+    for y in y0.max(0)..(y0 + size).min(dynamic.plane.height as i64) {
+        let (row, v) = (y as usize, (y - y0) as usize);
+        let (start, end) = ((columns.start - x0) as usize, (columns.end - x0) as usize);
+        let range = columns.start as usize..columns.end as usize;
+        dynamic.z[row][range.clone()].copy_from_slice(&tile.z[v][start..end]);
+        dynamic.dz[row][range.clone()].copy_from_slice(&tile.dz[v][start..end]);
+        dynamic.d[row][range.clone()].copy_from_slice(&tile.d[v][start..end]);
+        dynamic.period[row][range.clone()].copy_from_slice(&tile.period[v][start..end]);
+        dynamic.z_saved[row][range].copy_from_slice(&tile.z_saved[v][start..end]);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::holomorphic::formula::Formula;
+
+    /** a plane of a size in pixels around a point, with the pixels of a zoom level **/
+    fn view(re: f64, im: f64, level: i32, width: usize, height: usize) -> Plane<f64> {
+        let (w, h) = (width as f64 * pixel_size(level) / 2.0, height as f64 * pixel_size(level) / 2.0);
+        Plane {re_min: re - w, re_max: re + w, im_min: im - h, im_max: im + h, rotation: 0.0, width, height}
+    }
+
+    #[test]
+    fn snap() {
+        let plane = Plane {re_min: -0.7012, re_max: -0.3123, im_min: 0.1017, im_max: 0.4, rotation: 0.0, width: 400, height: 300};
+        let snapped = Tiles::snap(&plane).expect("snapped");
+        let pixel = snapped.pixel_size();
+        assert_eq!(pixel, pixel_size(level(&plane)));
+        assert_eq!((snapped.width, snapped.height), (400, 300));
+        for coordinate in [snapped.re_min, snapped.re_max, snapped.im_min, snapped.im_max] {
+            assert_eq!((coordinate / pixel).fract(), 0.0);
+        }
+        let moved = snapped.center() - plane.center();
+        assert!(moved.re.abs() <= pixel / 2.0 && moved.im.abs() <= pixel / 2.0);
+        assert_eq!(Tiles::snap(&snapped).map(|plane| (plane.re_min, plane.im_min)), Some((snapped.re_min, snapped.im_min)));
+
+        assert!(Tiles::snap(&Plane {rotation: 10.0, ..plane}).is_none());
+        assert!(Tiles::snap(&view(-0.75, 0.1, MAX_LEVEL + 1, 400, 300)).is_none());
+    }
+
+    #[test]
+    fn assemble_negative_tiles() {
+        /* the plane covers the corners of the tiles (-4, -1), (-3, -1), (-4, 0) and (-3, 0) */
+        let plane = Tiles::snap(&view(-0.75, 0.0, 4, 64, 48)).expect("snapped");
+        let tiles = Mutex::new(Tiles::new(&Session::default(), 16, None).expect("valid session"));
+        let mut published = 0;
+        let assembled = assemble(&tiles, plane, 50, |_| { published += 1; true }).expect("not cancelled");
+        /* after each batch of tiles */
+        let batches = 4_usize.div_ceil(rayon::current_num_threads());
+        assert_eq!((assembled.i, published, tiles.lock().expect("not poisoned").cached.len()), (50, batches, 4));
+
+        let mut direct = Session::default().dynamic().expect("valid session").with_plane(plane);
+        direct.steps(50);
+        assert!(assembled.d == direct.d && assembled.period == direct.period);
+
+        /* from memory, without publishing anything in between */
+        let cached = assemble(&tiles, plane, 50, |_| { published += 1; true }).expect("not cancelled");
+        assert_eq!(published, batches);
+        assert!(cached.d == direct.d);
+    }
+
+    #[test]
+    fn least_recently_used() {
+        let mut tiles = Tiles::new(&Session::default(), 2, None).expect("valid session");
+        let keys = [-1, 0, 1].map(|x| TileKey {level: 0, x, y: 0});
+        let mut tile = tiles.prototype.with_plane(keys[0].plane());
+        tile.i = 10;
+        tiles.insert(keys[0], &tile);
+        tiles.insert(keys[1], &tile);
+        assert!(tiles.cached(keys[0]).is_some());
+        tiles.insert(keys[2], &tile);
+        assert!(tiles.cached.contains_key(&keys[0]) && !tiles.cached.contains_key(&keys[1]) && tiles.cached.contains_key(&keys[2]));
+
+        /* a tile iterated further stays */
+        tile.i = 5;
+        tiles.insert(keys[0], &tile);
+        assert_eq!(tiles.cached(keys[0]).map(|tile| tile.i), Some(10));
+    }
+
+    #[test]
+    fn disk_cache() {
+        let directory = std::env::temp_dir().join(format!("fractals-tiles-{}", std::process::id()));
+        let plane = Tiles::snap(&view(-0.6, 0.35, 4, 32, 32)).expect("snapped");
+        let computed = Mutex::new(Tiles::new(&Session::default(), 16, Some(directory.clone())).expect("valid session"));
+        let expected = assemble(&computed, plane, 40, |_| true).expect("not cancelled");
+        let mut computed = computed.into_inner().expect("not poisoned");
+        assert_eq!(computed.disk_error(), None);
+
+        /* another process finds the tile on disk */
+        let loaded = Mutex::new(Tiles::new(&Session::default(), 16, Some(directory.clone())).expect("valid session"));
+        let mut published = 0;
+        let assembled = assemble(&loaded, plane, 40, |_| { published += 1; true }).expect("not cancelled");
+        assert_eq!(published, 0);
+        assert!(assembled.d == expected.d && assembled.z == expected.z && assembled.dz == expected.dz);
+
+        /* fewer iterations are computed again, but the tile on disk stays at 40 */
+        let fewer = assemble(&loaded, plane, 20, |_| { published += 1; true }).expect("not cancelled");
+        assert_eq!((fewer.i, published), (20, 1));
+        let key = TileKey {level: 4, x: (-0.6 * 1024.0 / 256.0_f64).floor() as i64, y: (0.35 * 1024.0 / 256.0_f64).floor() as i64};
+        let path = computed.path(key).expect("disk cache");
+        assert_eq!(checkpoint::load(&path).map(|(_, tile)| tile.i), Ok(40));
+        assert_eq!(loaded.lock().expect("not poisoned").cached(key).map(|tile| tile.i), Some(40));
+
+        /* another fractal doesn't take the tiles of this one, should their directories collide */
+        let other = Session {formula: Formula::parse("z^3 + c").expect("valid formula"), ..Session::default()};
+        let other = Tiles::new(&other, 16, Some(directory.clone())).expect("valid session");
+        assert!(load(&path, &computed.session).is_some() && load(&path, &other.session).is_none());
+        std::fs::remove_dir_all(&directory).expect("removed");
+    }
+}
@@ -0,0 +1,169 @@
+use num::Complex;
+use std::fmt;
+use super::{bigfixed::{BigComplex, BigFixed}, dynamic::HolomorphicDynamic, plane::Plane, precision::Precision};
+
+
+/** Decimal digits of the center beyond the order of magnitude of the scale, far below the size of a pixel **/
+const SUBSCALE_DIGITS: i32 = 12;
+
+/** Bits of the center beyond the printed digits, so parsing and printing it again yields the same digits **/
+const GUARD_BITS: u32 = 8;
+
+/** A view of the complex plane by its center, scale and rotation, independent of any resolution
+ *  - the center has arbitrary precision, so deep zooms can be shared as text like
+ *    "-0.743643887037158704752191506114774,0.131825904205311970493132056385139,1.5e-30"
+ *  - it is printed with the digits, which matter at its scale, and parsing the text again yields the same view
+ **/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Viewport {
+    pub center: BigComplex,
+    pub scale: f64,     // height of the view in the complex plane, its width follows from the aspect ratio
+    pub rotation: f64,  // counterclockwise in degrees
+}
+
+impl Viewport {
+    /** the view of a plane, which is relative to center, if given **/
+    pub fn from_plane<T: Precision>(plane: &Plane<T>, center: Option<&BigComplex>) -> Self {
+        let scale = (plane.im_max - plane.im_min).abs().to_f64().unwrap_or(f64::NAN);
+        let bits = bits(digits(scale)).max(center.map_or(0, |center| center.re.bits()));
+        let offset = plane.center();
+        let offset = BigComplex::from_f64(offset.re.to_f64().unwrap_or(0.0), offset.im.to_f64().unwrap_or(0.0), bits);
+        let center = center.map_or(offset.clone(), |center| center.with_bits(bits).add(&offset));
+        Self {center, scale, rotation: plane.rotation.to_f64().unwrap_or(0.0)}
+    }
+
+    /** the view rasterized with square pixels, in absolute coordinates as far as T allows **/
+    pub fn plane<T: Precision>(&self, width: usize, height: usize) -> Plane<T> {
+        let (re, im) = self.center.to_f64();
+        let plane = self.relative_plane::<f64>(width, height).translate(Complex::new(re, im));
+        let convert = |x: f64| T::from_f64(x).unwrap_or(T::nan());
+        Plane {
+            re_min: convert(plane.re_min),
+            re_max: convert(plane.re_max),
+            im_min: convert(plane.im_min),
+            im_max: convert(plane.im_max),
+            rotation: convert(plane.rotation),
+            width,
+            height,
+        }
+    }
+
+    /** the view rasterized with square pixels, relative to its center (for perturbation) **/
+    pub fn relative_plane<T: Precision>(&self, width: usize, height: usize) -> Plane<T> {
+        let half_height = self.scale / 2.0;
+        let half_width = half_height * width as f64 / height as f64;
+        let convert = |x: f64| T::from_f64(x).unwrap_or(T::nan());
+        Plane {
+            re_min: convert(-half_width),
+            re_max: convert(half_width),
+            im_min: convert(-half_height),
+            im_max: convert(half_height),
+            rotation: convert(self.rotation),
+            width,
+            height,
+        }
+    }
+
+    /** parses "RE,IM,SCALE" or "RE,IM,SCALE,ROTATION", where RE and IM may have any number of digits **/
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let parts = s.split(',').map(str::trim).collect::<Vec<_>>();
+        let (re, im, scale, rotation) = match parts[..] {
+            [re, im, scale] => (re, im, scale, "0"),
+            [re, im, scale, rotation] => (re, im, scale, rotation),
+            _ => return Err(format!("'{}': expected RE,IM,SCALE or RE,IM,SCALE,ROTATION", s)),
+        };
+        let scale = scale.parse::<f64>().map_err(|e| format!("'{}': {}", scale, e))?;
+        if !(scale.is_finite() && scale > 0.0) {
+            return Err(format!("'{}': the scale must be positive", s));
+        }
+        let rotation = rotation.parse::<f64>().map_err(|e| format!("'{}': {}", rotation, e))?;
+        if !rotation.is_finite() {
+            return Err(format!("'{}': the rotation must be finite", s));
+        }
+        let bits = bits(digits(scale));
+        let center = BigComplex {re: BigFixed::parse(re, bits)?, im: BigFixed::parse(im, bits)?};
+        Ok(Self {center, scale, rotation})
+    }
+}
+
+/** "RE,IM,SCALE" and ",ROTATION" if the view is rotated **/
+impl fmt::Display for Viewport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = digits(self.scale);
+        write!(f, "{},{},{:?}", self.center.re.to_decimal(digits), self.center.im.to_decimal(digits), self.scale)?;
+        if self.rotation != 0.0 {
+            write!(f, ",{:?}", self.rotation)?;
+        }
+        Ok(())
+    }
+}
+
+
+impl<T: Precision> HolomorphicDynamic<T> {
+    /** the view of the dynamic in absolute coordinates, also if it is perturbed **/
+    pub fn viewport(&self) -> Viewport {
+        Viewport::from_plane(&self.plane, self.perturbation.as_ref().map(|perturbation| &perturbation.center))
+    }
+
+    /** a dynamic of the same kind and function, restarted on a view in a resolution
+     *  - perturbed dynamics are computed relative to the center of the view, which is only valid for z² + c
+     **/
+    pub fn with_viewport(&self, viewport: &Viewport, width: usize, height: usize, perturbed: bool) -> Self {
+        let mut dynamic = if perturbed {
+            Self::new_perturbed(&viewport.center, viewport.relative_plane(width, height), self.f.clone())
+        } else {
+            Self::new(self.kind, viewport.plane(width, height), self.f.clone())
+        };
+        dynamic.roots = self.roots.clone();
+        dynamic.derivative = self.derivative;
+        dynamic
+    }
+}
+
+
+/** the fractional digits of the center, which resolve a view of this scale **/
+fn digits(scale: f64) -> u32 {
+    let magnitude = if scale.is_finite() && scale > 0.0 { -scale.log10().floor() as i32 } else { 0 };
+    (magnitude + SUBSCALE_DIGITS).max(0) as u32
+}
+
+/** the precision of the center, which holds this number of fractional digits **/
+fn bits(digits: u32) -> u32 {
+    (digits as f64 * 10.0_f64.log2()).ceil() as u32 + GUARD_BITS
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_display() {
+        for view in [
+            "-0.75,0.1,2.5",
+            "-0.75,0.1,2.5,30.0",
+            "-0.743643887037158704752191506114774,0.131825904205311970493132056385139,1.5e-30",
+            "-1.7499370775,0.0,3.2e-7,-90.0",
+        ] {
+            let viewport = Viewport::parse(view).expect("valid view");
+            assert_eq!(viewport.to_string(), view);
+            assert_eq!(Viewport::parse(&viewport.to_string()).expect("valid view"), viewport);
+        }
+        for view in ["", "1,2", "1,2,3,4,5", "1,2,0", "1,2,-1", "1,2,inf", "a,2,3", "1,2,3,nan"] {
+            assert!(Viewport::parse(view).is_err(), "{}", view);
+        }
+    }
+
+    #[test]
+    fn from_plane() {
+        let plane = Plane::<f64> {re_min: -2.0, re_max: 1.0, im_min: -1.0, im_max: 1.0, rotation: 15.0, width: 300, height: 200};
+        let viewport = Viewport::from_plane(&plane, None);
+        assert_eq!(viewport.to_string(), "-0.5,0.0,2.0,15.0");
+        let restored = viewport.plane::<f64>(plane.width, plane.height);
+        assert_eq!((restored.re_min, restored.re_max, restored.im_min, restored.im_max, restored.rotation), (-2.0, 1.0, -1.0, 1.0, 15.0));
+
+        let center = BigComplex::from_f64(-0.75, 0.125, 128);
+        let relative = Viewport::parse("0,0,3e-20").expect("valid view").relative_plane::<f64>(4, 3);
+        assert_eq!(Viewport::from_plane(&relative, Some(&center)).to_string(), "-0.75,0.125,3e-20");
+    }
+}
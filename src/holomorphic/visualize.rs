@@ -1,37 +1,147 @@
-use crate::coloring::{domain_coloring::domain_coloring, gradient::gradient_rgb};
-use sdl2::{gfx::primitives::DrawRenderer, render::WindowCanvas};
-use super::dynamic::HolomorphicDynamic;
+use crate::coloring::{color_space::ColorSpace, domain_coloring::domain_coloring, helper::{norm, norm_u8}, hsl::hsl_to_rgb, palette::Palette};
+use crate::image::Image;
+use super::{dynamic::HolomorphicDynamic, precision::{to_complex32, Precision}, simulation::{D_MAX, SKIPPED}};
 
 
+/** The quantity, which is visualized **/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Coloring {
+    Z,         // state
+    D,         // iteration of divergence
+    Smooth,    // continuous iteration of divergence
+    Histogram, // continuous iteration of divergence, equalized over the view
+    Distance,  // estimated distance to the set
+    Roots,     // basins of the Newton method
+    Period,    // cycles of interior points
+}
+
+impl Coloring {
+    pub const ALL: [Coloring; 7] = [Coloring::Z, Coloring::D, Coloring::Smooth, Coloring::Histogram, Coloring::Distance, Coloring::Roots, Coloring::Period];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Coloring::Z => "z",
+            Coloring::D => "d",
+            Coloring::Smooth => "smooth",
+            Coloring::Histogram => "histogram",
+            Coloring::Distance => "distance",
+            Coloring::Roots => "roots",
+            Coloring::Period => "period",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL.into_iter().find(|coloring| coloring.name() == name).ok_or_else(|| {
+            let names = Self::ALL.map(Coloring::name).join(", ");
+            format!("'{}': unknown coloring, use one of {}", name, names)
+        })
+    }
+}
+
 pub trait Visualize {
-    fn visualize_z(&self, canvas: &mut WindowCanvas);
-    fn visualize_d(&self, canvas: &mut WindowCanvas);
+    /** the palette colors the iterations of divergence (d, smooth and the outside of period), its color space
+     *  also applies to the domain coloring of z
+     **/
+    fn visualize(&self, coloring: Coloring, palette: &Palette) -> Image {
+        match coloring {
+            Coloring::Z => self.visualize_z(palette.space),
+            Coloring::D => self.visualize_d(palette),
+            Coloring::Smooth => self.visualize_smooth(palette),
+            Coloring::Histogram => self.visualize_histogram(palette),
+            Coloring::Distance => self.visualize_distance(),
+            Coloring::Roots => self.visualize_roots(),
+            Coloring::Period => self.visualize_period(palette),
+        }
+    }
+    fn visualize_z(&self, space: ColorSpace) -> Image;
+    fn visualize_d(&self, palette: &Palette) -> Image;
+    fn visualize_smooth(&self, palette: &Palette) -> Image;
+    fn visualize_histogram(&self, palette: &Palette) -> Image;
+    fn visualize_distance(&self) -> Image;
+    fn visualize_roots(&self) -> Image;
+    fn visualize_period(&self, palette: &Palette) -> Image;
     fn debug(&self, x: usize, y: usize);
 }
 
-impl Visualize for HolomorphicDynamic {
-    fn visualize_z<'a>(&self, canvas: &'a mut WindowCanvas) {
+impl<T: Precision> Visualize for HolomorphicDynamic<T> {
+    /** the state z at each coordinate, saturated by the iterations since divergence **/
+    fn visualize_z(&self, space: ColorSpace) -> Image {
         /* we assume the first divergence happened in one of the opposing corners or not yet */
         let first_divergence = self.d[0][0].min(self.d[self.plane.height-1][self.plane.width-1]).min(1);
         let s_steps = 20.0;
-        for y in 0..self.plane.height {
-            for x in 0..self.plane.width {
-                let s = (0.9 / s_steps) * ((self.d[y][x]-(first_divergence+1)) as f32).min(s_steps);
-                let color = domain_coloring(self.z[y][x], 2.0, s);
-                let _ = canvas.pixel(x as i16, y as i16, color);
+        Image::from_fn(self.plane.width, self.plane.height, |x, y| {
+            let s = (0.9 / s_steps) * ((self.d[y][x]-(first_divergence+1)) as f32).min(s_steps);
+            domain_coloring(to_complex32(self.z[y][x]), 2.0, s, space)
+        })
+    }
+
+    /** the iteration of divergence at each coordinate, black if it didn't diverge (yet) **/
+    fn visualize_d(&self, palette: &Palette) -> Image {
+        Image::from_fn(self.plane.width, self.plane.height, |x, y| {
+            match self.d[y][x] {
+                D_MAX => [0, 0, 0],
+                d => palette.color(d as f32),
             }
-        }
-        canvas.present();
+        })
     }
 
-    fn visualize_d<'a>(&self, canvas: &'a mut WindowCanvas) {
-        for y in 0..self.plane.height {
-            for x in 0..self.plane.width {
-                let color = gradient_rgb(self.d[y][x]);
-                let _ = canvas.pixel(x as i16, y as i16, color);
+    /** the continuous iteration of divergence at each coordinate, without banding **/
+    fn visualize_smooth(&self, palette: &Palette) -> Image {
+        let degree = self.degree();
+        Image::from_fn(self.plane.width, self.plane.height, |x, y| {
+            match self.d[y][x] {
+                D_MAX => [0, 0, 0],
+                _ => palette.color(self.smooth_d(x, y, degree) as f32),
             }
-        }
-        canvas.present();
+        })
+    }
+
+    /** the continuous iteration of divergence mapped by its cumulative distribution onto the whole palette, so
+     *  the colors adapt to the range of iterations of each view (offset and density of the palette don't apply)
+     **/
+    fn visualize_histogram(&self, palette: &Palette) -> Image {
+        let degree = self.degree();
+        let histogram = self.histogram();
+        Image::from_fn(self.plane.width, self.plane.height, |x, y| {
+            match self.d[y][x] {
+                D_MAX => [0, 0, 0],
+                _ => palette.color_at(histogram.position(self.smooth_d(x, y, degree)) as f32),
+            }
+        })
+    }
+
+    /** the estimated distance to the set at each coordinate, which reveals thin filaments
+     *  > black (set and boundary) -> white (more than 2 pixels away)
+     **/
+    fn visualize_distance(&self) -> Image {
+        Image::from_fn(self.plane.width, self.plane.height, |x, y| {
+            let v = self.distance(x, y).map_or(0, |distance| norm_u8((distance as f32).sqrt(), 0.0, 1.5));
+            [v, v, v]
+        })
+    }
+
+    /** the basin of the root reached by the Newton method, darker the slower it converged **/
+    fn visualize_roots(&self) -> Image {
+        let n = self.roots.len().max(1) as f32;
+        Image::from_fn(self.plane.width, self.plane.height, |x, y| {
+            match self.root(x, y) {
+                None => [0, 0, 0],
+                Some(root) => hsl_to_rgb(root as f32 / n, 0.8, 0.7 - 0.6 * norm(self.d[y][x] as f32, 0.0, 40.0)),
+            }
+        })
+    }
+
+    /** the period of the cycle inside of the set, outside like visualize_smooth **/
+    fn visualize_period(&self, palette: &Palette) -> Image {
+        let degree = self.degree();
+        Image::from_fn(self.plane.width, self.plane.height, |x, y| {
+            match self.period[y][x] {
+                0 | SKIPPED if self.d[y][x] == D_MAX => [0, 0, 0],
+                0 => palette.color(self.smooth_d(x, y, degree) as f32),
+                /* the golden ratio spreads the hues of neighbouring periods */
+                period => hsl_to_rgb((period as f32 * 0.618034).fract(), 0.6, 0.4),
+            }
+        })
     }
 
     fn debug(&self, x: usize, y: usize) {
@@ -39,8 +149,12 @@ impl Visualize for HolomorphicDynamic {
         let c = self.plane.xy_to_c(x, y);
         let (c_r, c_theta) = c.to_polar();
         println!("c(x,y) = {} = {}*e^i*{}", c, c_r, c_theta);
+        if let Some(perturbation) = &self.perturbation {
+            println!("c(x,y) is relative to the reference {}", perturbation.center);
+        }
         let z = self.z[y][x];
         let (z_r, z_theta) = z.to_polar();
-        println!("z(x,y) = {} = {}*e^i*{}, d(x,y) = {}", z, z_r, z_theta, self.d[y][x]); 
+        println!("z(x,y) = {} = {}*e^i*{}, d(x,y) = {}", z, z_r, z_theta, self.d[y][x]);
+        println!("dz(x,y) = {}, distance(x,y) = {:?}px, period(x,y) = {}", self.dz[y][x], self.distance(x, y), self.period[y][x]);
     }
 }
@@ -0,0 +1,130 @@
+use std::sync::{atomic::{AtomicBool, AtomicI32, Ordering}, mpsc::{self, Sender}, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use super::{dynamic::HolomorphicDynamic, plane::Plane, precision::Precision, simulation::Simulation, tiles::{self, Tiles}};
+
+
+/** Coarse passes precede the full resolution, each dividing width and height by its factor **/
+const PASSES: [usize; 2] = [8, 4];
+
+/** Minimal time between two published snapshots of the full resolution **/
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(100);
+
+/** Intermediate state of a background simulation **/
+pub struct Snapshot<T: Precision = f32> {
+    pub dynamic: HolomorphicDynamic<T>,  // may have a coarser plane than the simulated dynamic
+    pub complete: bool,                  // the target iteration was reached
+}
+
+/** Simulates a dynamic on a background thread
+ *  - first coarse passes (unless the dynamic took over computed coordinates), then batches of iterations of the full resolution are published as snapshots
+ *  - the simulation is cancelled, when the worker is dropped
+ **/
+pub struct Worker<T: Precision = f32> {
+    target: Arc<AtomicI32>,
+    cancelled: Arc<AtomicBool>,
+    receiver: mpsc::Receiver<Snapshot<T>>,
+}
+
+impl<T: Precision> Worker<T> {
+    pub fn spawn(dynamic: HolomorphicDynamic<T>, iterations: i32) -> Self {
+        let target = Arc::new(AtomicI32::new(iterations));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        {
+            let (target, cancelled) = (Arc::clone(&target), Arc::clone(&cancelled));
+            thread::spawn(move || simulate(dynamic, &target, &cancelled, &sender));
+        }
+        Self {target, cancelled, receiver}
+    }
+
+    /** the iteration, which the simulation runs up to **/
+    pub fn target(&self) -> i32 {
+        self.target.load(Ordering::Relaxed)
+    }
+
+    /** simulates more iterations, unless the simulation already completed **/
+    pub fn extend(&self, iterations: i32) {
+        self.target.fetch_add(iterations, Ordering::Relaxed);
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /** the most recent snapshot published since the last call **/
+    pub fn latest(&self) -> Option<Snapshot<T>> {
+        self.receiver.try_iter().last()
+    }
+}
+
+impl Worker<f64> {
+    /** assembles the dynamic of a plane snapped by Tiles::snap from tiles, then simulates it like spawn
+     *  - instead of coarse passes, the dynamic is published whenever more tiles are complete
+     **/
+    pub fn spawn_tiled(tiles: Arc<Mutex<Tiles>>, plane: Plane<f64>, iterations: i32) -> Self {
+        let target = Arc::new(AtomicI32::new(iterations));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        {
+            let (target, cancelled) = (Arc::clone(&target), Arc::clone(&cancelled));
+            thread::spawn(move || {
+                let assembled = tiles::assemble(&tiles, plane, target.load(Ordering::Relaxed), |dynamic| {
+                    !cancelled.load(Ordering::Relaxed) && sender.send(Snapshot {dynamic: dynamic.clone(), complete: false}).is_ok()
+                });
+                if let Some(dynamic) = assembled {
+                    simulate(dynamic, &target, &cancelled, &sender);
+                }
+            });
+        }
+        Self {target, cancelled, receiver}
+    }
+}
+
+impl<T: Precision> Drop for Worker<T> {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+fn simulate<T: Precision>(mut dynamic: HolomorphicDynamic<T>, target: &AtomicI32, cancelled: &AtomicBool, sender: &Sender<Snapshot<T>>) {
+    let running = || !cancelled.load(Ordering::Relaxed);
+
+    /* only a dynamic starting from scratch has nothing better to show than coarse passes */
+    if dynamic.i == 0 && dynamic.resumed == 0 && dynamic.i < target.load(Ordering::Relaxed) {
+        for factor in PASSES {
+            let plane = Plane {
+                width: (dynamic.plane.width / factor).max(1),
+                height: (dynamic.plane.height / factor).max(1),
+                ..dynamic.plane
+            };
+            let mut coarse = dynamic.with_plane(plane);
+            while coarse.i < target.load(Ordering::Relaxed) {
+                if !running() {
+                    return;
+                }
+                coarse.step();
+            }
+            if sender.send(Snapshot {dynamic: coarse, complete: false}).is_err() {
+                return;
+            }
+        }
+    }
+
+    loop {
+        let published = Instant::now();
+        while dynamic.i < target.load(Ordering::Relaxed) && published.elapsed() < PUBLISH_INTERVAL {
+            if !running() {
+                return;
+            }
+            dynamic.step();
+        }
+        if dynamic.i >= target.load(Ordering::Relaxed) {
+            let _ = sender.send(Snapshot {dynamic, complete: true});
+            return;
+        }
+        if sender.send(Snapshot {dynamic: dynamic.clone(), complete: false}).is_err() {
+            return;
+        }
+    }
+}
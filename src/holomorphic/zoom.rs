@@ -0,0 +1,110 @@
+use crate::coloring::{color_space::{encode, linear_to_srgb, srgb_to_linear}, Color};
+use crate::image::Image;
+use super::{plane::Plane, session::Session, simulation::Simulation, visualize::Visualize};
+
+
+/** Resolution of keyframes relative to the frames, which are resampled from them until they are zoomed in as far **/
+const KEYFRAME_SCALE: usize = 2;
+
+/** Zoom from a start view into the view of a session
+ *  - the frames are spaced evenly in the logarithm of the scale, so the zoom appears to have a constant speed
+ *  - the iterations increase with the depth, from start_iterations to those of the session
+ *  - frames are resampled from the last keyframe, while it contains them at least at their resolution
+ **/
+pub struct Zoom {
+    pub session: Session,    // the view of the last frame
+    pub start: Plane<f64>,   // the view of the first frame, relative to the center of the session if it has one
+    pub start_iterations: i32,
+    pub frames: usize,
+    keyframe: Option<(Plane<f64>, Image)>,
+}
+
+impl Zoom {
+    /** a zoom from an absolute viewport into the view of the session **/
+    pub fn new(session: Session, start: Plane<f64>, start_iterations: i32, frames: usize) -> Self {
+        let (re, im) = session.center.as_ref().map_or((0.0, 0.0), |center| center.to_f64());
+        let start = Plane {re_min: start.re_min - re, re_max: start.re_max - re, im_min: start.im_min - im, im_max: start.im_max - im, ..start};
+        Self {session, start, start_iterations, frames, keyframe: None}
+    }
+
+    /** the view of frame k and its iterations **/
+    pub fn view(&self, k: usize) -> (Plane<f64>, i32) {
+        let t = if self.frames > 1 { k as f64 / (self.frames - 1) as f64 } else { 1.0 };
+        (self.start.interpolate(&self.session.plane, t), self.iterations(t))
+    }
+
+    fn iterations(&self, t: f64) -> i32 {
+        let (start, end) = (self.start_iterations as f64, self.session.iterations as f64);
+        (start + (end - start) * t.clamp(0.0, 1.0)).round() as i32
+    }
+
+    /** renders frame k, the result tells whether a keyframe had to be simulated for it **/
+    pub fn frame(&mut self, k: usize) -> Result<(Image, bool), String> {
+        let (plane, _) = self.view(k);
+        let simulated = match &self.keyframe {
+            Some((keyplane, _)) if contains(keyplane, &plane) => false,
+            _ => {
+                self.keyframe = Some(self.keyframe(k)?);
+                true
+            }
+        };
+        let (keyplane, keyframe) = self.keyframe.as_ref().expect("keyframe was just rendered");
+        Ok((resample(keyframe, keyplane, &plane), simulated))
+    }
+
+    /** the view of frame k at a higher resolution with the iterations of the deepest frame, which it may serve **/
+    fn keyframe(&self, k: usize) -> Result<(Plane<f64>, Image), String> {
+        let (plane, iterations) = self.view(k);
+        let plane = Plane {width: plane.width * KEYFRAME_SCALE, height: plane.height * KEYFRAME_SCALE, ..plane};
+        /* the fraction of the zoom, which magnifies by KEYFRAME_SCALE */
+        let span = |plane: &Plane<f64>| plane.re_max - plane.re_min;
+        let depth = (KEYFRAME_SCALE as f64).ln() / (span(&self.start) / span(&self.session.plane)).ln().abs();
+        let t = if self.frames > 1 { k as f64 / (self.frames - 1) as f64 } else { 1.0 };
+        let iterations = iterations.max(self.iterations(t + depth));
+
+        let session = Session {plane, iterations, ..self.session.clone()};
+        let mut dynamic = session.dynamic()?;
+        dynamic.steps(iterations);
+        Ok((plane, dynamic.visualize(session.coloring, &session.palette)))
+    }
+}
+
+
+/** whether the keyframe covers the plane with pixels at least as small **/
+fn contains(keyplane: &Plane<f64>, plane: &Plane<f64>) -> bool {
+    let corners = [(0, 0), (plane.width, 0), (0, plane.height), (plane.width, plane.height)];
+    /* a fraction of a pixel of tolerance for rounding */
+    corners.iter().all(|&(x, y)| {
+        let (u, v) = keyplane.c_to_xy(plane.xy_to_c(x, y));
+        (-0.5..=keyplane.width as f64 + 0.5).contains(&u) && (-0.5..=keyplane.height as f64 + 0.5).contains(&v)
+    }) && keyplane.pixel_size().abs() <= plane.pixel_size().abs() * (1.0 + 1.0e-9)
+}
+
+/** the plane sampled from the keyframe with a tent filter as wide as the pixels of the plane, in linear light **/
+fn resample(keyframe: &Image, keyplane: &Plane<f64>, plane: &Plane<f64>) -> Image {
+    let radius_x = (plane.re_max - plane.re_min) / plane.width as f64 * keyframe.width as f64 / (keyplane.re_max - keyplane.re_min);
+    let radius_y = (plane.im_max - plane.im_min) / plane.height as f64 * keyframe.height as f64 / (keyplane.im_max - keyplane.im_min);
+    /* the keyframe pixels around u with their weights */
+    let taps = |u: f64, radius: f64, size: usize| {
+        let first = (u - radius).ceil().max(0.0) as usize;
+        let last = ((u + radius).floor().max(0.0) as usize).min(size - 1);
+        (first..=last).map(move |k| (k, (1.0 - (k as f64 - u).abs() / radius).max(0.0) as f32))
+    };
+
+    Image::from_fn(plane.width, plane.height, |x, y| {
+        let (u, v) = keyplane.c_to_xy(plane.xy_to_c(x, y));
+        let (mut sum, mut total): (Color, f32) = ([0.0; 3], 0.0);
+        for (j, w_y) in taps(v, radius_y, keyframe.height) {
+            for (i, w_x) in taps(u, radius_x, keyframe.width) {
+                let color = srgb_to_linear(keyframe.pixel(i, j).map(|c| c as f32 / 255.0));
+                let w = w_x * w_y;
+                sum = [0, 1, 2].map(|c| sum[c] + w * color[c]);
+                total += w;
+            }
+        }
+        match total > 0.0 {
+            true => encode(linear_to_srgb(sum.map(|c| c / total))),
+            false => [0, 0, 0],
+        }
+    })
+}
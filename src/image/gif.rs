@@ -0,0 +1,332 @@
+use std::{collections::HashMap, io::Write, time::Duration};
+use crate::coloring::Rgb;
+use super::Image;
+
+
+/** Colors of the table of each frame **/
+const COLORS: usize = 256;
+
+/** Largest code of the LZW compression **/
+const MAX_CODE: u16 = 4095;
+
+/** Animated GIF, which loops forever
+ *  - frames are written one by one, so an animation doesn't need to fit into memory
+ *  - each frame is quantized to its own table of 256 colors by median cut
+ **/
+pub struct GifWriter<W: Write> {
+    writer: W,
+    width: usize,
+    height: usize,
+    delay: u16,  // between frames in 1/100 s
+}
+
+impl<W: Write> GifWriter<W> {
+    pub fn new(mut writer: W, width: usize, height: usize, delay: Duration) -> Result<Self, String> {
+        let (w, h) = (u16::try_from(width), u16::try_from(height));
+        let (Ok(w), Ok(h)) = (w, h) else {
+            return Err(format!("{}x{} is too large for a GIF", width, height));
+        };
+        let delay = (delay.as_millis() / 10).clamp(1, u16::MAX as u128) as u16;
+        let io = |e: std::io::Error| e.to_string();
+        writer.write_all(b"GIF89a").map_err(io)?;
+        writer.write_all(&w.to_le_bytes()).map_err(io)?;
+        writer.write_all(&h.to_le_bytes()).map_err(io)?;
+        /* no global color table, 8 bit color resolution, background and aspect ratio unused */
+        writer.write_all(&[0x70, 0, 0]).map_err(io)?;
+        /* the Netscape extension: loop forever */
+        writer.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00").map_err(io)?;
+        Ok(Self {writer, width, height, delay})
+    }
+
+    pub fn write_frame(&mut self, image: &Image) -> Result<(), String> {
+        if (image.width, image.height) != (self.width, self.height) {
+            return Err(format!("a frame of {}x{} in a GIF of {}x{}", image.width, image.height, self.width, self.height));
+        }
+        let (table, indices) = quantize(image);
+        let io = |e: std::io::Error| e.to_string();
+        let writer = &mut self.writer;
+        /* graphic control extension: keep the frame, no transparency */
+        writer.write_all(&[0x21, 0xf9, 4, 0x04]).map_err(io)?;
+        writer.write_all(&self.delay.to_le_bytes()).map_err(io)?;
+        writer.write_all(&[0, 0]).map_err(io)?;
+        /* image descriptor with a local color table of 2^(7+1) colors */
+        writer.write_all(&[0x2c, 0, 0, 0, 0]).map_err(io)?;
+        writer.write_all(&(self.width as u16).to_le_bytes()).map_err(io)?;
+        writer.write_all(&(self.height as u16).to_le_bytes()).map_err(io)?;
+        writer.write_all(&[0x87]).map_err(io)?;
+        for k in 0..COLORS {
+            writer.write_all(&table.get(k).copied().unwrap_or([0, 0, 0])).map_err(io)?;
+        }
+        writer.write_all(&[8]).map_err(io)?;
+        for block in lzw(&indices).chunks(255) {
+            writer.write_all(&[block.len() as u8]).map_err(io)?;
+            writer.write_all(block).map_err(io)?;
+        }
+        writer.write_all(&[0]).map_err(io)
+    }
+
+    pub fn finish(mut self) -> Result<W, String> {
+        self.writer.write_all(&[0x3b]).and_then(|_| self.writer.flush()).map_err(|e| e.to_string())?;
+        Ok(self.writer)
+    }
+}
+
+
+/** the colors of an image reduced to 5 bits per channel, the key of the histogram **/
+fn key([r, g, b]: Rgb) -> usize {
+    ((r as usize >> 3) << 10) | ((g as usize >> 3) << 5) | (b as usize >> 3)
+}
+
+/** a table of at most 256 colors and the index into it for each pixel
+ *  - the box of colors with the widest channel is split at its median, until there are 256 boxes
+ *  - each box is represented by the mean of its colors
+ **/
+fn quantize(image: &Image) -> (Vec<Rgb>, Vec<u8>) {
+    /* count and sum of the colors with the same key */
+    let mut histogram = vec![(0_u64, [0_u64; 3]); 1 << 15];
+    for pixel in image.data.chunks_exact(3) {
+        let entry = &mut histogram[key([pixel[0], pixel[1], pixel[2]])];
+        entry.0 += 1;
+        for (sum, &c) in entry.1.iter_mut().zip(pixel) {
+            *sum += c as u64;
+        }
+    }
+    let colors = histogram.iter().enumerate().filter(|(_, (count, _))| *count > 0).map(|(key, _)| key).collect::<Vec<_>>();
+    let channel = |key: usize, c: usize| (key >> (10 - 5 * c)) & 31;
+
+    let mut boxes = vec![colors];
+    while boxes.len() < COLORS {
+        let range = |colors: &Vec<usize>, c: usize| {
+            let (min, max) = colors.iter().fold((31, 0), |(min, max), &key| (min.min(channel(key, c)), max.max(channel(key, c))));
+            max.saturating_sub(min)
+        };
+        let widest = (0..boxes.len())
+            .flat_map(|k| (0..3).map(move |c| (k, c)))
+            .max_by_key(|&(k, c)| range(&boxes[k], c));
+        let Some((k, c)) = widest.filter(|&(k, c)| range(&boxes[k], c) > 0) else { break };
+
+        let mut colors = boxes.swap_remove(k);
+        colors.sort_by_key(|&key| channel(key, c));
+        let total = colors.iter().map(|&key| histogram[key].0).sum::<u64>();
+        let mut count = 0;
+        let median = colors.iter().position(|&key| {
+            count += histogram[key].0;
+            2 * count >= total
+        }).unwrap_or(0);
+        /* both halves keep at least one color */
+        let upper = colors.split_off((median + 1).min(colors.len() - 1));
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    let table = boxes.iter().map(|colors| {
+        let count = colors.iter().map(|&key| histogram[key].0).sum::<u64>().max(1);
+        [0, 1, 2].map(|c| (colors.iter().map(|&key| histogram[key].1[c]).sum::<u64>() / count) as u8)
+    }).collect::<Vec<Rgb>>();
+    let mut index = vec![0_u8; 1 << 15];
+    for (k, colors) in boxes.iter().enumerate() {
+        for &key in colors {
+            index[key] = k as u8;
+        }
+    }
+    let indices = image.data.chunks_exact(3).map(|pixel| index[key([pixel[0], pixel[1], pixel[2]])]).collect();
+    (table, indices)
+}
+
+/** the variable length LZW compression of GIF for 8 bit indices **/
+fn lzw(indices: &[u8]) -> Vec<u8> {
+    const CLEAR: u16 = 256;
+    const END: u16 = 257;
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0_u32, 0);
+    let mut emit = |code: u16, size: u32| {
+        buffer |= (code as u32) << bits;
+        bits += size;
+        while bits >= 8 {
+            bytes.push(buffer as u8);
+            buffer >>= 8;
+            bits -= 8;
+        }
+    };
+
+    let mut dictionary = HashMap::<(u16, u8), u16>::new();
+    let (mut next, mut size) = (END + 1, 9);
+    emit(CLEAR, size);
+    let Some((&first, rest)) = indices.split_first() else {
+        emit(END, size);
+        return finish(bytes, buffer, bits);
+    };
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = dictionary.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        emit(prefix, size);
+        if next <= MAX_CODE {
+            dictionary.insert((prefix, index), next);
+            /* the decoder widens the codes one entry later */
+            if next == 1 << size {
+                size += 1;
+            }
+            next += 1;
+        } else {
+            emit(CLEAR, size);
+            dictionary.clear();
+            (next, size) = (END + 1, 9);
+        }
+        prefix = index as u16;
+    }
+    emit(prefix, size);
+    emit(END, size);
+    finish(bytes, buffer, bits)
+}
+
+fn finish(mut bytes: Vec<u8>, buffer: u32, bits: u32) -> Vec<u8> {
+    if bits > 0 {
+        bytes.push(buffer as u8);
+    }
+    bytes
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /** the indices of the LZW codes of a frame and the largest code size, as a GIF decoder reads them **/
+    fn decode(bytes: &[u8]) -> (Vec<u8>, u32, usize) {
+        const CLEAR: usize = 256;
+        const END: usize = 257;
+        let mut indices = Vec::new();
+        let (mut widest, mut clears) = (0, 0);
+        let mut dictionary = Vec::<Vec<u8>>::new();
+        let mut previous: Option<Vec<u8>> = None;
+        let (mut position, mut size) = (0, 9);
+        loop {
+            let code = (0..size).fold(0, |code, bit| {
+                let byte = bytes.get((position + bit) / 8).expect("END before the end of the data");
+                code | (((byte >> ((position + bit) % 8)) & 1) as usize) << bit
+            });
+            position += size;
+            widest = widest.max(size as u32);
+            if code == CLEAR {
+                dictionary = (0..=255).map(|index| vec![index]).chain([vec![], vec![]]).collect();
+                (previous, size, clears) = (None, 9, clears + 1);
+                continue;
+            }
+            if code == END {
+                break;
+            }
+            let entry = match (dictionary.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) if code == dictionary.len() => [previous.as_slice(), &previous[..1]].concat(),
+                _ => panic!("code {} beyond the dictionary of {}", code, dictionary.len()),
+            };
+            indices.extend_from_slice(&entry);
+            if let Some(previous) = previous.filter(|_| dictionary.len() <= MAX_CODE as usize) {
+                dictionary.push([previous.as_slice(), &entry[..1]].concat());
+            }
+            if dictionary.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+            previous = Some(entry);
+        }
+        assert_eq!(position.div_ceil(8), bytes.len(), "data after END");
+        (indices, widest, clears)
+    }
+
+    /** a reproducible sequence of pseudo random bytes below `limit` **/
+    fn random(n: usize, limit: u32) -> Vec<u8> {
+        let mut state = 0x2545f491_u32;
+        (0..n).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state % limit) as u8
+        }).collect()
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        /* an empty frame is only the CLEAR and END code */
+        assert_eq!(lzw(&[]), [0x00, 0x03, 0x02]);
+        assert_eq!(decode(&lzw(&[])), (vec![], 9, 1));
+
+        /* a code that the decoder doesn't know yet, as it is the entry being defined */
+        assert_eq!(decode(&lzw(&[7; 10])).0, [7; 10]);
+
+        /* the codes widen from 9 to 10 bits, once the dictionary has 512 entries */
+        let widening = random(600, 256);
+        let (indices, widest, clears) = decode(&lzw(&widening));
+        assert!(indices == widening);
+        assert_eq!((widest, clears), (10, 1));
+
+        /* the dictionary is cleared repeatedly, once its codes are used up */
+        let clearing = random(50000, 256);
+        let (indices, widest, clears) = decode(&lzw(&clearing));
+        assert!(indices == clearing);
+        assert_eq!(widest, 12);
+        assert!(clears > 5, "{} clears", clears);
+    }
+
+    #[test]
+    fn quantize_colors() {
+        /* up to 256 colors with distinct keys are kept exactly */
+        let image = Image::from_fn(16, 16, |x, y| [(x * 16) as u8, (y * 16) as u8, ((x + y) * 8) as u8]);
+        let (table, indices) = quantize(&image);
+        assert_eq!(table.len(), 256);
+        for (pixel, &index) in image.data.chunks_exact(3).zip(&indices) {
+            assert_eq!(table[index as usize], pixel);
+        }
+
+        /* 4096 colors are approximated by 256, each close to its pixels */
+        let image = Image::from_fn(64, 64, |x, y| [(x * 4) as u8, (y * 4) as u8, ((x / 4 + y / 4 * 16) % 256) as u8]);
+        let (table, indices) = quantize(&image);
+        assert_eq!(table.len(), 256);
+        for (pixel, &index) in image.data.chunks_exact(3).zip(&indices) {
+            let error = table[index as usize].iter().zip(pixel).map(|(&a, &b)| a.abs_diff(b)).max().unwrap_or(0);
+            assert!(error <= 32, "{:?} for {:?}", table[index as usize], pixel);
+        }
+
+        /* an empty image has a table with one unused color */
+        let (table, indices) = quantize(&Image::new(0, 0));
+        assert_eq!((table.len(), indices.len()), (1, 0));
+    }
+
+    #[test]
+    fn frames() {
+        let image = Image::from_fn(300, 2, |x, y| [x as u8, (x / 2) as u8, (y * 255) as u8]);
+        let mut writer = GifWriter::new(Vec::new(), 300, 2, Duration::from_millis(50)).expect("valid size");
+        writer.write_frame(&image).expect("frame of the size of the GIF");
+        assert!(writer.write_frame(&Image::new(2, 2)).is_err());
+        let bytes = writer.finish().expect("written to memory");
+
+        /* the header, the loop extension, the graphic control extension and the image descriptor */
+        let header = 6 + 7 + 19;
+        assert_eq!(&bytes[..6], b"GIF89a");
+        assert_eq!(&bytes[header..header + 8], [0x21, 0xf9, 4, 0x04, 5, 0, 0, 0]);
+        let table = &bytes[header + 18..header + 18 + 3 * COLORS];
+        assert_eq!(bytes[header + 18 + 3 * COLORS], 8);
+        let mut data = Vec::new();
+        let mut blocks = &bytes[header + 19 + 3 * COLORS..];
+        while blocks[0] > 0 {
+            let (block, rest) = blocks[1..].split_at(blocks[0] as usize);
+            data.extend_from_slice(block);
+            blocks = rest;
+        }
+        assert_eq!(&blocks[1..], [0x3b]);
+
+        let (indices, _, _) = decode(&data);
+        let (expected, _) = quantize(&image);
+        assert_eq!(indices.len(), 600);
+        for (k, &index) in indices.iter().enumerate() {
+            assert_eq!(table[3 * index as usize..3 * index as usize + 3], expected[index as usize], "pixel {}", k);
+        }
+
+        /* a GIF without pixels still has a frame */
+        let mut writer = GifWriter::new(Vec::new(), 0, 0, Duration::ZERO).expect("valid size");
+        writer.write_frame(&Image::new(0, 0)).expect("empty frame");
+        assert_eq!(writer.finish().expect("written to memory").last(), Some(&0x3b));
+    }
+}
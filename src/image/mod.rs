@@ -0,0 +1,61 @@
+pub mod gif;
+pub mod png;
+pub mod ppm;
+
+use crate::coloring::Rgb;
+use rayon::prelude::*;
+use std::{fs::File, io::BufWriter, path::Path, time::Duration};
+
+
+/** In-memory RGB24 image, rows from top to bottom
+ *  - can be uploaded into SDL textures (see crate::sdl::image) or written to files
+ **/
+#[derive(Clone)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,  // 3 bytes per pixel
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {width, height, data: vec![0; 3*width*height]}
+    }
+
+    /** the image with the color of each coordinate, calculated in parallel **/
+    pub fn from_fn(width: usize, height: usize, color: impl Fn(usize, usize) -> Rgb + Sync) -> Self {
+        let mut image = Self::new(width, height);
+        if width > 0 {
+            image.data.par_chunks_mut(3*width).enumerate().for_each(|(y, row)| {
+                for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                    pixel.copy_from_slice(&color(x, y));
+                }
+            });
+        }
+        image
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Rgb {
+        let i = 3 * (y*self.width + x);
+        [self.data[i], self.data[i+1], self.data[i+2]]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Rgb) {
+        let i = 3 * (y*self.width + x);
+        self.data[i..i+3].copy_from_slice(&color);
+    }
+
+    /** writes the image in the format given by the file extension (.png, .ppm or .gif) **/
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        /* the format is known before the file is created, so unknown ones don't leave an empty file behind */
+        let write: fn(&Self, BufWriter<File>) -> Result<(), String> = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("png") => |image, writer| png::write_png(image, writer),
+            Some("ppm") => |image, writer| ppm::write_ppm(image, writer),
+            Some("gif") => |image, writer| gif::GifWriter::new(writer, image.width, image.height, Duration::ZERO)
+                .and_then(|mut gif| gif.write_frame(image).and_then(|_| gif.finish()).map(|_| ())),
+            _ => return Err(format!("{}: unknown image format, use .png, .ppm or .gif", path.display())),
+        };
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        write(self, BufWriter::new(file))
+    }
+}
@@ -0,0 +1,12 @@
+use std::io::Write;
+use super::Image;
+
+
+pub fn write_png(image: &Image, writer: impl Write) -> Result<(), String> {
+    let mut encoder = ::png::Encoder::new(writer, image.width as u32, image.height as u32);
+    encoder.set_color(::png::ColorType::Rgb);
+    encoder.set_depth(::png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&image.data).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())
+}
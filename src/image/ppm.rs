@@ -0,0 +1,10 @@
+use std::io::Write;
+use super::Image;
+
+
+/** binary portable pixmap (P6), which can be written without any dependency **/
+pub fn write_ppm(image: &Image, mut writer: impl Write) -> Result<(), String> {
+    write!(writer, "P6\n{} {}\n255\n", image.width, image.height).map_err(|e| e.to_string())?;
+    writer.write_all(&image.data).map_err(|e| e.to_string())?;
+    writer.flush().map_err(|e| e.to_string())
+}
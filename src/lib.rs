@@ -1,3 +1,5 @@
 pub mod sdl;
 pub mod coloring;
 pub mod holomorphic;
+pub mod image;
+pub mod cli;
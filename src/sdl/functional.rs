@@ -1,6 +1,8 @@
 extern crate sdl2;
 
 use std::{cell::RefCell, rc::Rc};
+use crate::image::Image;
+use super::image::image_into_texture;
 
 
 #[derive(Clone)]
@@ -67,12 +69,12 @@ impl FunctionalSDL {
     }
 
     pub fn map_into_texture(&self, width: u32, height: u32, f: impl Fn(usize) -> [u8; 3]) -> Result<sdl2::render::Texture, String> {
-        let pixels: usize = (width*height).try_into().unwrap();
-        let pitch: usize = (3*height).try_into().unwrap();
-        let mut texture = self.create_texture(width, height)?;
-        let rect = None;
-        let pixel_data = &(0..pixels).map(f).collect::<Vec<[u8; 3]>>().concat();
-        let _ = texture.update(rect, pixel_data, pitch);
-        Ok(texture)
+        let (width, height) = (width as usize, height as usize);
+        let data = (0..width*height).map(f).collect::<Vec<[u8; 3]>>().concat();
+        self.image_into_texture(&Image {width, height, data})
+    }
+
+    pub fn image_into_texture(&self, image: &Image) -> Result<sdl2::render::Texture<'_>, String> {
+        image_into_texture(&self.texture_creator, image)
     }
 }
@@ -0,0 +1,17 @@
+use crate::image::Image;
+use sdl2::{pixels::PixelFormatEnum, render::{Texture, TextureCreator}};
+
+
+/** Uploads an image into a new streaming texture **/
+pub fn image_into_texture<'a, C>(texture_creator: &'a TextureCreator<C>, image: &Image) -> Result<Texture<'a>, String> {
+    let mut texture = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGB24, image.width as u32, image.height as u32)
+        .map_err(|e| e.to_string())?;
+    update_texture(&mut texture, image)?;
+    Ok(texture)
+}
+
+/** Replaces the content of a texture with the same size as the image **/
+pub fn update_texture(texture: &mut Texture, image: &Image) -> Result<(), String> {
+    texture.update(None, &image.data, 3*image.width).map_err(|e| e.to_string())
+}